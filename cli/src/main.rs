@@ -0,0 +1,160 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use planarity_core::{
+    generate::{GenerateConfig, Generator},
+    level::{LevelFile, Stats},
+};
+use ron::ser::PrettyConfig;
+
+/// Make and check planarity puzzles without opening the game
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate puzzles into level files
+    Generate {
+        /// How many puzzles to make
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: u64,
+        /// The seed of the first puzzle, with each one after using the next
+        /// seed along
+        #[arg(long)]
+        seed: Option<u64>,
+        /// How to generate puzzles: circles, delaunay, maximal, grid,
+        /// triangular, outerplanar or tree
+        #[arg(long)]
+        generator: Option<Generator>,
+        /// Change a generation setting, with the value written in RON, like
+        /// `num_circles=5`
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        settings: Vec<String>,
+        /// How far from the middle to draw each solution
+        #[arg(long, default_value_t = 200.)]
+        radius: f32,
+        /// Where to write the levels
+        #[arg(short, long, default_value = ".")]
+        out: PathBuf,
+    },
+    /// Check that levels have no crossings
+    Check {
+        files: Vec<PathBuf>,
+        /// Check each level's solution rather than its starting layout
+        #[arg(long)]
+        solution: bool,
+    },
+    /// Print statistics about levels
+    Stats { files: Vec<PathBuf> },
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Generate {
+            count,
+            seed,
+            generator,
+            settings,
+            radius,
+            out,
+        } => generate(count, seed, generator, &settings, radius, &out),
+        Command::Check { files, solution } => check(&files, solution),
+        Command::Stats { files } => stats(&files),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn generate(
+    count: u64,
+    seed: Option<u64>,
+    generator: Option<Generator>,
+    settings: &[String],
+    radius: f32,
+    out: &PathBuf,
+) -> Result<bool, String> {
+    let mut cfg = generate_config(settings)?;
+    if let Some(generator) = generator {
+        cfg.generator = generator;
+    }
+    fs::create_dir_all(out).map_err(|error| format!("{}: {error}", out.display()))?;
+
+    let first = seed.unwrap_or_else(rand::random);
+    for seed in (0..count).map(|index| first.wrapping_add(index)) {
+        let level = LevelFile::generate(seed, &cfg, radius)
+            .map_err(|error| format!("seed {seed}: {error}"))?;
+        let path = out.join(format!("puzzle-{seed}.level"));
+        let text = ron::ser::to_string_pretty(&level, PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        fs::write(&path, text).map_err(|error| format!("{}: {error}", path.display()))?;
+        println!("{}\n{}\n", path.display(), Stats::of(&level));
+    }
+    Ok(true)
+}
+
+fn check(files: &[PathBuf], solution: bool) -> Result<bool, String> {
+    let mut all_untangled = true;
+    for path in files {
+        let level = read_level(path)?;
+        let crossings = if solution {
+            level
+                .solution_crossings()
+                .ok_or_else(|| format!("{} has no solution", path.display()))?
+        } else {
+            level.layout_crossings()
+        };
+        println!("{}: {crossings} crossings", path.display());
+        all_untangled &= crossings == 0;
+    }
+    Ok(all_untangled)
+}
+
+fn stats(files: &[PathBuf]) -> Result<bool, String> {
+    for path in files {
+        let level = read_level(path)?;
+        println!("{}\n{}\n", path.display(), Stats::of(&level));
+    }
+    Ok(true)
+}
+
+/// The default generation settings with each `NAME=VALUE` written over them,
+/// read as one RON struct so every value is written as in a settings file
+fn generate_config(settings: &[String]) -> Result<GenerateConfig, String> {
+    let mut fields: Vec<(&str, &str)> = Vec::new();
+    for setting in settings {
+        let (name, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("{setting} should be NAME=VALUE"))?;
+        let name = name.trim();
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("unknown setting {name}"));
+        }
+        // one whole value, so it can't spill over into other settings
+        ron::from_str::<ron::Value>(value).map_err(|error| format!("{name}: {error}"))?;
+        ron::from_str::<GenerateConfig>(&format!("({name}: {value})"))
+            .map_err(|error| format!("{name}: {}", error.code))?;
+        fields.retain(|(field, _)| *field != name);
+        fields.push((name, value));
+    }
+    let text = fields
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    ron::from_str(&format!("({text})")).map_err(|error| error.code.to_string())
+}
+
+fn read_level(path: &PathBuf) -> Result<LevelFile, String> {
+    let bytes = fs::read(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    LevelFile::parse(&bytes).map_err(|error| format!("{}: {error}", path.display()))
+}
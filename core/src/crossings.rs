@@ -0,0 +1,383 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+};
+
+use geo::{
+    kernels::{Kernel, Orientation, RobustKernel},
+    Coord,
+};
+use glam::{DVec2, Vec2};
+use itertools::Itertools;
+
+use crate::geometry::{segment_intersection, SegmentIntersection};
+
+use self::status::Status;
+
+mod status;
+
+/// Whether two edges, given by the nodes at their ends and the lines they're
+/// drawn along, count as crossing each other
+pub fn edges_cross<N: PartialEq>(
+    (x_ends, x_line): ([N; 2], [Vec2; 2]),
+    (y_ends, y_line): ([N; 2], [Vec2; 2]),
+) -> bool {
+    let shared = x_ends.iter().filter(|node| y_ends.contains(node)).count();
+    // two edges joining the same nodes are drawn as one line
+    shared < 2 && counts_as_crossing(segment_intersection(x_line, y_line), shared > 0)
+}
+
+/// The rules for which ways of meeting count as a crossing. Edges sharing a
+/// node always touch there, so they only cross by folding over each other, and
+/// stacking a node onto another node or onto an edge leaves a puzzle unsolved.
+pub fn counts_as_crossing(intersection: SegmentIntersection, adjacent: bool) -> bool {
+    match intersection {
+        SegmentIntersection::Disjoint => false,
+        SegmentIntersection::Touching => !adjacent,
+        SegmentIntersection::Proper
+        | SegmentIntersection::CollinearOverlap
+        | SegmentIntersection::VertexOnEdge => true,
+    }
+}
+
+/// A point ordered the way the sweep line reaches it: left to right, then
+/// bottom to top
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SweepPoint(DVec2);
+
+impl Eq for SweepPoint {}
+
+impl PartialOrd for SweepPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SweepPoint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .x
+            .total_cmp(&other.0.x)
+            .then_with(|| self.0.y.total_cmp(&other.0.y))
+    }
+}
+
+/// Everything that happens when the sweep line reaches a point
+#[derive(Debug, Default)]
+struct Events {
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+    /// Neighbours to swap, lower one first
+    crossings: Vec<(usize, usize)>,
+}
+
+/// Find every pair of segments for which `crosses` holds, using a
+/// Bentley–Ottmann sweep so that only segments that meet or are neighbours at
+/// some point of the sweep get compared.
+///
+/// `crosses` is only ever called on pairs that touch or come close to each
+/// other, with the lower index first, and decides what counts as a crossing.
+/// Returned pairs are sorted and have the lower index first.
+pub fn sweep_crossings(
+    segments: &[[Vec2; 2]],
+    mut crosses: impl FnMut(usize, usize) -> bool,
+) -> Vec<(usize, usize)> {
+    // orient every segment so it starts at the end the sweep line meets first
+    let lines = segments
+        .iter()
+        .map(|&[start, end]| {
+            let (start, end) = (start.as_dvec2(), end.as_dvec2());
+            if SweepPoint(start) <= SweepPoint(end) {
+                [start, end]
+            } else {
+                [end, start]
+            }
+        })
+        .collect_vec();
+
+    let mut queue: BTreeMap<SweepPoint, Events> = BTreeMap::new();
+    for (index, [start, end]) in lines.iter().enumerate() {
+        queue
+            .entry(SweepPoint(*start))
+            .or_default()
+            .starts
+            .push(index);
+        queue.entry(SweepPoint(*end)).or_default().ends.push(index);
+    }
+
+    let mut sweep = Sweep {
+        lines: &lines,
+        queue,
+        status: Status::new(lines.len()),
+        candidates: HashSet::new(),
+        pending: HashSet::new(),
+    };
+    while let Some((point, events)) = sweep.queue.pop_first() {
+        for &(lower, upper) in &events.crossings {
+            sweep.swap(lower, upper, point);
+        }
+        if !events.starts.is_empty() || !events.ends.is_empty() {
+            sweep.reach_endpoint(point, &events);
+        }
+    }
+
+    let mut crossings = sweep
+        .candidates
+        .into_iter()
+        .filter(|&(a, b)| crosses(a, b))
+        .collect_vec();
+    crossings.sort_unstable();
+    crossings
+}
+
+struct Sweep<'a> {
+    lines: &'a [[DVec2; 2]],
+    queue: BTreeMap<SweepPoint, Events>,
+    status: Status,
+    /// Pairs that meet, or were neighbours at some point
+    candidates: HashSet<(usize, usize)>,
+    /// Neighbours whose crossing is waiting in the queue
+    pending: HashSet<(usize, usize)>,
+}
+
+impl Sweep<'_> {
+    /// Swap two neighbours where they cross
+    fn swap(&mut self, lower: usize, upper: usize, point: SweepPoint) {
+        self.pending.remove(&(lower, upper));
+        // they may have been split up, or already swapped, since this was queued
+        if !self.status.contains(lower) || self.status.next(lower) != Some(upper) {
+            return;
+        }
+        self.status.remove(upper);
+        self.status.insert_before(upper, Some(lower));
+        if let Some(below) = self.status.prev(upper) {
+            self.neighbours(below, upper, point);
+        }
+        if let Some(above) = self.status.next(lower) {
+            self.neighbours(lower, above, point);
+        }
+    }
+
+    /// Handle segments starting and ending at a point, along with any others
+    /// running through it
+    fn reach_endpoint(&mut self, point: SweepPoint, events: &Events) {
+        let lines = self.lines;
+        let SweepPoint(at) = point;
+        // segments running through the point are found exactly, since an
+        // endpoint isn't rounded, and sit together with everything below the
+        // point before them
+        let first = self
+            .status
+            .partition_point(|segment| orient(lines[segment], at) == Orientation::CounterClockwise);
+        let mut through = Vec::new();
+        let mut above = first;
+        while let Some(segment) = above {
+            if orient(lines[segment], at) != Orientation::Collinear {
+                break;
+            }
+            through.push(segment);
+            above = self.status.next(segment);
+        }
+
+        // everything that meets at this point gets compared with everything else here
+        let meeting = through
+            .iter()
+            .chain(&events.starts)
+            .chain(&events.ends)
+            .copied()
+            .unique()
+            .collect_vec();
+        for (&a, &b) in meeting.iter().tuple_combinations() {
+            self.candidates.insert(sort_pair(a, b));
+        }
+
+        for &segment in through.iter().chain(&events.ends) {
+            // an ending only missed by the search if rounding disordered the status
+            if self.status.contains(segment) {
+                self.status.remove(segment);
+            }
+        }
+        let below = match above {
+            Some(above) => self.status.prev(above),
+            None => self.status.last(),
+        };
+
+        // segments leaving this point are reinserted in the order they leave it,
+        // which swaps any pair crossing here
+        let mut block = through
+            .iter()
+            .chain(&events.starts)
+            .filter(|segment| !events.ends.contains(segment))
+            .copied()
+            .collect_vec();
+        block.sort_by(|&a, &b| {
+            match RobustKernel::orient2d(coord(at), coord(lines[a][1]), coord(lines[b][1])) {
+                Orientation::CounterClockwise => Ordering::Less,
+                Orientation::Clockwise => Ordering::Greater,
+                // overlapping segments can go either way round
+                Orientation::Collinear => a.cmp(&b),
+            }
+        });
+        for &segment in &block {
+            self.status.insert_before(segment, above);
+        }
+
+        match (block.first(), block.last()) {
+            (Some(&first), Some(&last)) => {
+                if let Some(below) = below {
+                    self.neighbours(below, first, point);
+                }
+                if let Some(above) = above {
+                    self.neighbours(last, above, point);
+                }
+            }
+            _ => {
+                if let (Some(below), Some(above)) = (below, above) {
+                    self.neighbours(below, above, point);
+                }
+            }
+        }
+    }
+
+    /// Two segments have just become neighbours, so queue their crossing if
+    /// they cross further along
+    fn neighbours(&mut self, lower: usize, upper: usize, point: SweepPoint) {
+        use Orientation::{Clockwise, CounterClockwise};
+
+        self.candidates.insert(sort_pair(lower, upper));
+        let [low, high] = [self.lines[lower], self.lines[upper]];
+        // the upper one has to come from above the lower one and end below it
+        let crosses_ahead = orient(low, high[0]) == CounterClockwise
+            && orient(low, high[1]) == Clockwise
+            && orient(high, low[0]) == Clockwise
+            && orient(high, low[1]) == CounterClockwise;
+        if crosses_ahead && self.pending.insert((lower, upper)) {
+            // rounding can put the crossing just behind the sweep line, so
+            // handle it as soon as possible instead
+            let at = SweepPoint(crossing_point(low, high)).max(point);
+            self.queue
+                .entry(at)
+                .or_default()
+                .crossings
+                .push((lower, upper));
+        }
+    }
+}
+
+fn sort_pair(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn coord(point: DVec2) -> Coord<f64> {
+    Coord {
+        x: point.x,
+        y: point.y,
+    }
+}
+
+/// Which side of a segment a point is on, exactly
+fn orient([start, end]: [DVec2; 2], point: DVec2) -> Orientation {
+    RobustKernel::orient2d(coord(start), coord(end), coord(point))
+}
+
+/// Where two segments known to cross properly do so
+fn crossing_point([p1, p2]: [DVec2; 2], [q1, q2]: [DVec2; 2]) -> DVec2 {
+    let p = p2 - p1;
+    let q = q2 - q1;
+    let t = (q1 - p1).perp_dot(q) / p.perp_dot(q);
+    p1 + t * p
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn brute_force(segments: &[[Vec2; 2]]) -> Vec<(usize, usize)> {
+        (0..segments.len())
+            .tuple_combinations()
+            .filter(|&(a, b)| meet(segments, a, b))
+            .collect()
+    }
+
+    fn meet(segments: &[[Vec2; 2]], a: usize, b: usize) -> bool {
+        segment_intersection(segments[a], segments[b]) != SegmentIntersection::Disjoint
+    }
+
+    fn grid_segments(rng: &mut impl Rng, count: usize, size: i32) -> Vec<[Vec2; 2]> {
+        let mut point = || Vec2::new(rng.gen_range(0..size) as f32, rng.gen_range(0..size) as f32);
+        (0..count).map(|_| [point(), point()]).collect()
+    }
+
+    #[test]
+    fn crossing_beside_a_vertex_on_an_edge() {
+        let segments = [
+            [Vec2::new(1., 0.), Vec2::new(3., 1.)],
+            [Vec2::new(3., 0.), Vec2::new(0., 2.)],
+            [Vec2::new(0., 0.), Vec2::new(2., 0.)],
+        ];
+        assert_eq!(
+            sweep_crossings(&segments, |a, b| meet(&segments, a, b)),
+            [(0, 1), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn vertical_and_collinear_segments() {
+        let segments = [
+            [Vec2::new(1., 0.), Vec2::new(1., 3.)],
+            [Vec2::new(0., 2.), Vec2::new(2., 1.)],
+            [Vec2::new(1., 1.), Vec2::new(1., 4.)],
+            [Vec2::new(2., 4.), Vec2::new(3., 4.)],
+            [Vec2::new(2., 2.), Vec2::new(2., 2.)],
+        ];
+        assert_eq!(
+            sweep_crossings(&segments, |a, b| meet(&segments, a, b)),
+            brute_force(&segments)
+        );
+    }
+
+    #[test]
+    fn matches_brute_force_on_a_grid() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for trial in 0..2000 {
+            let size = [3, 5, 8][trial % 3];
+            let segments = grid_segments(&mut rng, 2 + trial % 12, size);
+            assert_eq!(
+                sweep_crossings(&segments, |a, b| meet(&segments, a, b)),
+                brute_force(&segments),
+                "{segments:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_brute_force_on_many_segments() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for size in [10, 50, 1000] {
+            let segments = grid_segments(&mut rng, 200, size);
+            assert_eq!(
+                sweep_crossings(&segments, |a, b| meet(&segments, a, b)),
+                brute_force(&segments)
+            );
+        }
+    }
+
+    #[test]
+    fn only_reports_what_crosses_counts() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let segments = grid_segments(&mut rng, 100, 20);
+        let proper = |a: usize, b: usize| {
+            segment_intersection(segments[a], segments[b]) == SegmentIntersection::Proper
+        };
+        assert_eq!(
+            sweep_crossings(&segments, proper),
+            (0..segments.len())
+                .tuple_combinations()
+                .filter(|&(a, b)| proper(a, b))
+                .collect_vec()
+        );
+    }
+}
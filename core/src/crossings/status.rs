@@ -0,0 +1,205 @@
+/// The segments cut by the sweep line, from bottom to top.
+///
+/// Each segment is a node of a treap, found by its index, so inserting next to
+/// a segment, removing one and stepping to a neighbour never compare segments
+/// with each other, and rounding can't leave a segment stuck in the status.
+pub(super) struct Status {
+    links: Vec<Link>,
+    root: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Link {
+    present: bool,
+    parent: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl Status {
+    /// An empty status for segments numbered below `len`
+    pub(super) fn new(len: usize) -> Self {
+        Self {
+            links: vec![Link::default(); len],
+            root: None,
+        }
+    }
+
+    pub(super) fn contains(&self, segment: usize) -> bool {
+        self.links[segment].present
+    }
+
+    /// The lowest segment that isn't `below`, given that every segment `below`
+    /// holds for comes before every one it doesn't
+    pub(super) fn partition_point(&self, mut below: impl FnMut(usize) -> bool) -> Option<usize> {
+        let mut node = self.root;
+        let mut found = None;
+        while let Some(current) = node {
+            if below(current) {
+                node = self.links[current].right;
+            } else {
+                found = Some(current);
+                node = self.links[current].left;
+            }
+        }
+        found
+    }
+
+    pub(super) fn last(&self) -> Option<usize> {
+        self.root.map(|root| self.rightmost(root))
+    }
+
+    /// The segment just above `segment`
+    pub(super) fn next(&self, segment: usize) -> Option<usize> {
+        if let Some(right) = self.links[segment].right {
+            return Some(self.leftmost(right));
+        }
+        let mut node = segment;
+        while let Some(parent) = self.links[node].parent {
+            if self.links[parent].left == Some(node) {
+                return Some(parent);
+            }
+            node = parent;
+        }
+        None
+    }
+
+    /// The segment just below `segment`
+    pub(super) fn prev(&self, segment: usize) -> Option<usize> {
+        if let Some(left) = self.links[segment].left {
+            return Some(self.rightmost(left));
+        }
+        let mut node = segment;
+        while let Some(parent) = self.links[node].parent {
+            if self.links[parent].right == Some(node) {
+                return Some(parent);
+            }
+            node = parent;
+        }
+        None
+    }
+
+    /// Insert `segment` just below `above`, or at the top if that's `None`
+    pub(super) fn insert_before(&mut self, segment: usize, above: Option<usize>) {
+        debug_assert!(!self.contains(segment));
+        self.links[segment] = Link {
+            present: true,
+            ..Link::default()
+        };
+        match above {
+            None => match self.root {
+                None => self.root = Some(segment),
+                Some(root) => {
+                    let last = self.rightmost(root);
+                    self.links[last].right = Some(segment);
+                    self.links[segment].parent = Some(last);
+                }
+            },
+            Some(above) => match self.links[above].left {
+                None => {
+                    self.links[above].left = Some(segment);
+                    self.links[segment].parent = Some(above);
+                }
+                Some(left) => {
+                    let previous = self.rightmost(left);
+                    self.links[previous].right = Some(segment);
+                    self.links[segment].parent = Some(previous);
+                }
+            },
+        }
+        while let Some(parent) = self.links[segment].parent {
+            if priority(parent) >= priority(segment) {
+                break;
+            }
+            self.rotate_up(segment);
+        }
+    }
+
+    pub(super) fn remove(&mut self, segment: usize) {
+        debug_assert!(self.contains(segment));
+        // rotate it down until it's a leaf, which can then be cut off
+        loop {
+            let Link { left, right, .. } = self.links[segment];
+            let child = match (left, right) {
+                (None, None) => break,
+                (Some(child), None) | (None, Some(child)) => child,
+                (Some(left), Some(right)) => {
+                    if priority(left) > priority(right) {
+                        left
+                    } else {
+                        right
+                    }
+                }
+            };
+            self.rotate_up(child);
+        }
+        match self.links[segment].parent {
+            None => self.root = None,
+            Some(parent) => {
+                let link = &mut self.links[parent];
+                if link.left == Some(segment) {
+                    link.left = None;
+                } else {
+                    link.right = None;
+                }
+            }
+        }
+        self.links[segment] = Link::default();
+    }
+
+    fn leftmost(&self, mut node: usize) -> usize {
+        while let Some(left) = self.links[node].left {
+            node = left;
+        }
+        node
+    }
+
+    fn rightmost(&self, mut node: usize) -> usize {
+        while let Some(right) = self.links[node].right {
+            node = right;
+        }
+        node
+    }
+
+    /// Swap a node with its parent, keeping the order of the segments
+    fn rotate_up(&mut self, node: usize) {
+        let parent = self.links[node].parent.expect("the root can't rotate up");
+        let grandparent = self.links[parent].parent;
+        let moved = if self.links[parent].left == Some(node) {
+            let moved = self.links[node].right;
+            self.links[parent].left = moved;
+            self.links[node].right = Some(parent);
+            moved
+        } else {
+            let moved = self.links[node].left;
+            self.links[parent].right = moved;
+            self.links[node].left = Some(parent);
+            moved
+        };
+        if let Some(moved) = moved {
+            self.links[moved].parent = Some(parent);
+        }
+        self.links[parent].parent = Some(node);
+        self.links[node].parent = grandparent;
+        match grandparent {
+            None => self.root = Some(node),
+            Some(grandparent) => {
+                let link = &mut self.links[grandparent];
+                if link.left == Some(parent) {
+                    link.left = Some(node);
+                } else {
+                    link.right = Some(node);
+                }
+            }
+        }
+    }
+}
+
+/// A treap priority for each segment, scrambled with splitmix64 so the tree
+/// stays balanced whatever order segments arrive in
+fn priority(segment: usize) -> u64 {
+    let mut z = (segment as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,174 @@
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectResource;
+#[cfg(feature = "bevy")]
+use bevy_reflect::{std_traits::ReflectDefault, Reflect};
+use glam::Vec2;
+use itertools::Itertools;
+use petgraph::{
+    prelude::*,
+    unionfind::UnionFind,
+    visit::{IntoEdgeReferences, NodeIndexable},
+};
+
+use crate::crossings::{edges_cross, sweep_crossings};
+
+/// How hard a puzzle looks, from the layout it was generated in
+#[derive(Default, Clone, Debug)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::system::Resource, Reflect),
+    reflect(Resource, Default)
+)]
+pub struct Difficulty {
+    pub score: f32,
+    pub crossings: u32,
+    pub crossings_per_edge: f32,
+    pub mean_degree: f32,
+    /// Standard deviation of the nodes' degrees
+    pub degree_spread: f32,
+    /// Faces of a drawing with no crossings, including the outside
+    pub faces: u32,
+    /// Roughly how many nodes have to move before nothing crosses
+    pub nodes_to_move: u32,
+}
+
+impl Difficulty {
+    pub fn of<E>(graph: &StableGraph<Vec2, E, Undirected>) -> Self {
+        let node_count = graph.node_count();
+        let edge_count = graph.edge_count();
+        let crossing_pairs = crossing_pairs(graph);
+        let crossings = crossing_pairs.len() as u32;
+        let crossings_per_edge = crossings as f32 / edge_count.max(1) as f32;
+
+        let degrees = graph
+            .node_indices()
+            .map(|node| graph.neighbors(node).count() as f32)
+            .collect_vec();
+        let mean_degree = degrees.iter().sum::<f32>() / node_count.max(1) as f32;
+        let degree_spread = (degrees
+            .iter()
+            .map(|degree| (degree - mean_degree).powi(2))
+            .sum::<f32>()
+            / node_count.max(1) as f32)
+            .sqrt();
+
+        // Euler's formula, for planar graphs in any number of pieces
+        let faces = (edge_count + 1 + components(graph)).saturating_sub(node_count) as u32;
+        let nodes_to_move = nodes_to_move(crossing_pairs) as u32;
+
+        // crossings matter most, but lots of crossings round a few nodes are
+        // easier than a few spread everywhere, and denser graphs are harder to
+        // see through
+        let score = 4. * crossings_per_edge
+            + (1. + crossings as f32).ln()
+            + 0.5 * nodes_to_move as f32
+            + (mean_degree - 2.).max(0.)
+            + 0.5 * degree_spread
+            + 0.1 * faces as f32;
+        Self {
+            score,
+            crossings,
+            crossings_per_edge,
+            mean_degree,
+            degree_spread,
+            faces,
+            nodes_to_move,
+        }
+    }
+}
+
+pub fn count_crossings<E>(graph: &StableGraph<Vec2, E, Undirected>) -> usize {
+    crossing_pairs(graph).len()
+}
+
+/// The ends of every pair of edges that cross
+fn crossing_pairs<E>(graph: &StableGraph<Vec2, E, Undirected>) -> Vec<[NodeIndex; 4]> {
+    let lines = graph
+        .edge_references()
+        .map(|edge| {
+            let (start, end) = (edge.source(), edge.target());
+            ([start, end], [graph[start], graph[end]])
+        })
+        .collect_vec();
+    let segments = lines.iter().map(|(_, line)| *line).collect_vec();
+    sweep_crossings(&segments, |x, y| edges_cross(lines[x], lines[y]))
+        .into_iter()
+        .map(|(x, y)| {
+            let ([x_start, x_end], [y_start, y_end]) = (lines[x].0, lines[y].0);
+            [x_start, x_end, y_start, y_end]
+        })
+        .collect()
+}
+
+/// Greedily take away whichever node is in the most crossings until none are
+/// left, which is an upper bound on how many nodes need moving
+fn nodes_to_move(mut pairs: Vec<[NodeIndex; 4]>) -> usize {
+    let mut moved = 0;
+    while let Some(node) = pairs
+        .iter()
+        .flatten()
+        .copied()
+        .counts()
+        .into_iter()
+        // the lowest index on ties, so the same graph always gives the same count
+        .max_by_key(|(node, count)| (*count, std::cmp::Reverse(*node)))
+        .map(|(node, _)| node)
+    {
+        pairs.retain(|pair| !pair.contains(&node));
+        moved += 1;
+    }
+    moved
+}
+
+fn components<E>(graph: &StableGraph<Vec2, E, Undirected>) -> usize {
+    let mut pieces = UnionFind::new(graph.node_bound());
+    for edge in graph.edge_references() {
+        pieces.union(edge.source().index(), edge.target().index());
+    }
+    graph
+        .node_indices()
+        .map(|node| pieces.find(node.index()))
+        .unique()
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    #[test]
+    fn sweep_counts_the_same_crossings_as_every_pair() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..50 {
+            let mut graph = StableUnGraph::<Vec2, ()>::default();
+            // a small grid, so plenty of edges touch and overlap
+            let nodes = (0..12)
+                .map(|_| {
+                    graph.add_node(Vec2::new(
+                        rng.gen_range(0..4) as f32,
+                        rng.gen_range(0..4) as f32,
+                    ))
+                })
+                .collect_vec();
+            for _ in 0..20 {
+                let (a, b) = (rng.gen_range(0..12), rng.gen_range(0..12));
+                if a != b {
+                    graph.add_edge(nodes[a], nodes[b], ());
+                }
+            }
+            let every_pair = graph
+                .edge_references()
+                .map(|edge| {
+                    let ends = [edge.source(), edge.target()];
+                    (ends, ends.map(|node| graph[node]))
+                })
+                .tuple_combinations()
+                .filter(|(x, y)| edges_cross(*x, *y))
+                .count();
+            assert_eq!(count_crossings(&graph), every_pair);
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    f64::consts::{FRAC_PI_2, TAU},
+};
+
+use glam::{DVec2, I64Vec2, Vec2};
+use itertools::Itertools;
+use petgraph::{prelude::*, stable_graph::IndexType};
+
+use crate::{
+    crossings::{edges_cross, sweep_crossings},
+    planarity::{rotation_system, simple_edges},
+};
+
+/// Find a straight-line drawing of a planar graph with no crossings, fitting
+/// inside a circle of the given radius round the origin. Returns `None` if the
+/// graph isn't planar.
+///
+/// The graph is triangulated, then drawn with one triangle pinned round the
+/// outside and every other node at the average position of its neighbours,
+/// which is Tutte's barycentric embedding. That can squeeze nodes together
+/// exponentially, so if rounding makes edges cross or nodes end up closer than
+/// [`min_spacing`], it's drawn on a grid instead, by de Fraysseix, Pach and
+/// Pollack's shifting method.
+pub fn planar_drawing<N, E, Ix: IndexType>(
+    graph: &StableGraph<N, E, Undirected, Ix>,
+    radius: f32,
+) -> Option<HashMap<NodeIndex<Ix>, Vec2>> {
+    let (nodes, edges) = simple_edges(graph);
+    let corners = |count: usize| {
+        (0..count).map(move |corner| {
+            DVec2::from_angle(TAU * corner as f64 / count as f64 + FRAC_PI_2) * radius as f64
+        })
+    };
+    if nodes.len() <= 3 {
+        return Some(
+            nodes
+                .into_iter()
+                .zip(corners(3).map(|corner| corner.as_vec2()))
+                .collect(),
+        );
+    }
+
+    // ordered sets, so the same graph is always drawn the same way
+    let mut adjacency = vec![BTreeSet::new(); nodes.len()];
+    for &(_, (a, b)) in &edges {
+        adjacency[a].insert(b);
+        adjacency[b].insert(a);
+    }
+    // joining separate pieces by one edge can't stop a graph being planar
+    for [a, b] in components(&adjacency).array_windows() {
+        adjacency[*a].insert(*b);
+        adjacency[*b].insert(*a);
+    }
+
+    let pairs = edge_pairs(&adjacency);
+    let rotation = rotation_system(nodes.len(), &pairs)?;
+    let mut triangles = Vec::new();
+    for face in faces(&rotation) {
+        triangulate(face, &mut adjacency, &mut triangles)?;
+    }
+    if edge_pairs(&adjacency).len() != 3 * nodes.len() - 6 {
+        return None;
+    }
+
+    let outer = *triangles.first()?;
+    let mut fixed = vec![None; nodes.len()];
+    for (node, corner) in outer.into_iter().zip(corners(3)) {
+        fixed[node] = Some(corner);
+    }
+    let mut positions = barycentric(&adjacency, &fixed)
+        .into_iter()
+        .map(|position| position.as_vec2())
+        .collect_vec();
+    let pairs = edges.iter().map(|(_, pair)| *pair).collect_vec();
+    if crosses_itself(&positions, &pairs)
+        || closest_distance(&positions) < min_spacing(radius, nodes.len())
+    {
+        positions = grid_drawing(&adjacency, outer, radius)?;
+        debug_assert!(!crosses_itself(&positions, &pairs));
+    }
+    Some(nodes.into_iter().zip(positions).collect())
+}
+
+/// How close together nodes can be in a drawing from [`planar_drawing`] of a
+/// graph with `node_count` nodes, which the grid drawing always manages
+pub fn min_spacing(radius: f32, node_count: usize) -> f32 {
+    radius / (2 * node_count.max(1)) as f32
+}
+
+fn crosses_itself(positions: &[Vec2], edges: &[(usize, usize)]) -> bool {
+    let lines = edges
+        .iter()
+        .map(|&(a, b)| [positions[a], positions[b]])
+        .collect_vec();
+    !sweep_crossings(&lines, |x, y| {
+        let ends = |edge: usize| [edges[edge].0, edges[edge].1];
+        edges_cross((ends(x), lines[x]), (ends(y), lines[y]))
+    })
+    .is_empty()
+}
+
+/// The distance between the two closest nodes
+fn closest_distance(positions: &[Vec2]) -> f32 {
+    let mut sorted = positions.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x));
+    let mut closest = f32::INFINITY;
+    for (index, a) in sorted.iter().enumerate() {
+        for b in &sorted[index + 1..] {
+            if b.x - a.x >= closest {
+                break;
+            }
+            closest = closest.min(a.distance(*b));
+        }
+    }
+    closest
+}
+
+fn edge_pairs(adjacency: &[BTreeSet<usize>]) -> Vec<(usize, usize)> {
+    adjacency
+        .iter()
+        .enumerate()
+        .flat_map(|(node, neighbours)| {
+            neighbours
+                .iter()
+                .filter(move |other| node < **other)
+                .map(move |other| (node, *other))
+        })
+        .collect()
+}
+
+/// One node from each connected component
+fn components(adjacency: &[BTreeSet<usize>]) -> Vec<usize> {
+    let mut seen = vec![false; adjacency.len()];
+    let mut representatives = Vec::new();
+    for start in 0..adjacency.len() {
+        if seen[start] {
+            continue;
+        }
+        representatives.push(start);
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for &other in &adjacency[node] {
+                if !seen[other] {
+                    seen[other] = true;
+                    stack.push(other);
+                }
+            }
+        }
+    }
+    representatives
+}
+
+/// Walks round the boundary of each face of an embedding
+fn faces(rotation: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let position: HashMap<(usize, usize), usize> = rotation
+        .iter()
+        .enumerate()
+        .flat_map(|(node, neighbours)| {
+            neighbours
+                .iter()
+                .enumerate()
+                .map(move |(index, other)| ((node, *other), index))
+        })
+        .collect();
+    let mut visited = HashSet::new();
+    let mut faces = Vec::new();
+    for (node, neighbours) in rotation.iter().enumerate() {
+        for &other in neighbours {
+            let mut dart = (node, other);
+            if visited.contains(&dart) {
+                continue;
+            }
+            let mut face = Vec::new();
+            while visited.insert(dart) {
+                let (from, to) = dart;
+                face.push(from);
+                // leave by the next edge clockwise from the one we arrived on
+                let around = &rotation[to];
+                dart = (to, around[(position[&(to, from)] + 1) % around.len()]);
+            }
+            faces.push(face);
+        }
+    }
+    faces
+}
+
+/// Split a face into triangles by repeatedly cutting off a corner whose
+/// neighbours along the boundary aren't already joined
+fn triangulate(
+    mut face: Vec<usize>,
+    adjacency: &mut [BTreeSet<usize>],
+    triangles: &mut Vec<[usize; 3]>,
+) -> Option<()> {
+    while face.len() > 3 {
+        let len = face.len();
+        let corner = (0..len).find(|&index| {
+            let (previous, next) = (face[(index + len - 1) % len], face[(index + 1) % len]);
+            previous != next && !adjacency[previous].contains(&next)
+        })?;
+        let (previous, node, next) = (
+            face[(corner + len - 1) % len],
+            face[corner],
+            face[(corner + 1) % len],
+        );
+        adjacency[previous].insert(next);
+        adjacency[next].insert(previous);
+        triangles.push([previous, node, next]);
+        face.remove(corner);
+    }
+    if let [a, b, c] = face[..] {
+        triangles.push([a, b, c]);
+    }
+    Some(())
+}
+
+/// Draw a triangulation with integer coordinates on a grid 2n-4 wide and n-2
+/// high, by de Fraysseix, Pach and Pollack's shifting method, then scale it to
+/// fit in the circle
+fn grid_drawing(
+    adjacency: &[BTreeSet<usize>],
+    outer: [usize; 3],
+    radius: f32,
+) -> Option<Vec<Vec2>> {
+    let order = canonical_order(adjacency, outer)?;
+
+    let mut grid = vec![I64Vec2::ZERO; adjacency.len()];
+    grid[order[1]] = I64Vec2::new(2, 0);
+    grid[order[2]] = I64Vec2::new(1, 1);
+    // the nodes that move along with each node on the contour
+    let mut dragged = (0..adjacency.len()).map(|node| vec![node]).collect_vec();
+    let mut contour = vec![order[0], order[2], order[1]];
+    for &node in &order[3..] {
+        // a canonical order puts the earlier neighbours together on the contour
+        let (first, last) = contour
+            .iter()
+            .positions(|other| adjacency[node].contains(other))
+            .minmax()
+            .into_option()?;
+        for (index, other) in contour.iter().enumerate().skip(first + 1) {
+            let shift = if index < last { 1 } else { 2 };
+            for moved in &dragged[*other] {
+                grid[*moved].x += shift;
+            }
+        }
+        let (left, right) = (grid[contour[first]], grid[contour[last]]);
+        // where the lines at 45 degrees up from the left and right ends meet
+        grid[node] = I64Vec2::new(
+            (left.x + right.x + right.y - left.y) / 2,
+            (right.x - left.x + left.y + right.y) / 2,
+        );
+        let covered = contour.splice(first + 1..last, [node]).collect_vec();
+        for other in covered {
+            let moved = std::mem::take(&mut dragged[other]);
+            dragged[node].extend(moved);
+        }
+    }
+
+    let (min, max) = grid.iter().fold((grid[0], grid[0]), |(min, max), point| {
+        (min.min(*point), max.max(*point))
+    });
+    let centre = (min + max).as_dvec2() / 2.;
+    let furthest = grid
+        .iter()
+        .map(|point| point.as_dvec2().distance(centre))
+        .fold(0., f64::max)
+        .max(1.);
+    let scale = radius as f64 / furthest;
+    Some(
+        grid.into_iter()
+            .map(|point| ((point.as_dvec2() - centre) * scale).as_vec2())
+            .collect(),
+    )
+}
+
+/// Order the nodes of a triangulation so that each one after the first two
+/// joins on to a run of the outside of the ones before it, by peeling nodes
+/// off the outside of the whole graph. Starts with two of the outer triangle
+/// and ends with the third.
+fn canonical_order(
+    adjacency: &[BTreeSet<usize>],
+    [first, second, last]: [usize; 3],
+) -> Option<Vec<usize>> {
+    let count = adjacency.len();
+    let rotation = rotation_system(count, &edge_pairs(adjacency))?;
+    // the outside of the nodes left, as a path from `first` to `second`
+    let mut previous = vec![None; count];
+    let mut next = vec![None; count];
+    let mut outside = vec![false; count];
+    let mut removed = vec![false; count];
+    // edges between nodes on the outside that aren't along it
+    let mut chords = vec![0usize; count];
+    for [a, b] in [[first, last], [last, second]] {
+        next[a] = Some(b);
+        previous[b] = Some(a);
+    }
+    for node in [first, second, last] {
+        outside[node] = true;
+    }
+    let is_chord = |a: usize, b: usize, previous: &[Option<usize>], next: &[Option<usize>]| {
+        previous[a] != Some(b)
+            && next[a] != Some(b)
+            && [a, b] != [first, second]
+            && [a, b] != [second, first]
+    };
+
+    let mut order = Vec::with_capacity(count);
+    for _ in 3..count {
+        // a node on the outside with no chords can always be found
+        let mut candidate = next[first];
+        let node = loop {
+            let node = candidate.filter(|node| *node != second)?;
+            if chords[node] == 0 {
+                break node;
+            }
+            candidate = next[node];
+        };
+        let (left, right) = (previous[node]?, next[node]?);
+        order.push(node);
+        removed[node] = true;
+        outside[node] = false;
+
+        // the neighbours left go round from one side to the other the way that
+        // doesn't pass straight between them
+        let around = rotation[node]
+            .iter()
+            .copied()
+            .filter(|other| !removed[*other])
+            .collect_vec();
+        let start = around.iter().position(|other| *other == left)?;
+        let step = if around[(start + 1) % around.len()] == right && around.len() > 2 {
+            around.len() - 1
+        } else {
+            1
+        };
+        let path = (0..around.len())
+            .map(|offset| around[(start + offset * step) % around.len()])
+            .take_while_inclusive(|other| *other != right)
+            .collect_vec();
+        if path.last() != Some(&right) {
+            return None;
+        }
+
+        if path.len() == 2 && is_chord(left, right, &previous, &next) {
+            chords[left] -= 1;
+            chords[right] -= 1;
+        }
+        for [a, b] in path.array_windows() {
+            next[*a] = Some(*b);
+            previous[*b] = Some(*a);
+        }
+        let new = &path[1..path.len() - 1];
+        for &node in new {
+            outside[node] = true;
+        }
+        for &node in new {
+            for &other in &adjacency[node] {
+                if outside[other] && is_chord(node, other, &previous, &next) {
+                    chords[node] += 1;
+                    if !new.contains(&other) {
+                        chords[other] += 1;
+                    }
+                }
+            }
+        }
+    }
+    let third = next[first]?;
+    order.extend([third, second, first]);
+    order.reverse();
+    Some(order)
+}
+
+/// Place every node that isn't fixed at the average position of its
+/// neighbours, by solving the resulting linear system with conjugate gradients
+fn barycentric(adjacency: &[BTreeSet<usize>], fixed: &[Option<DVec2>]) -> Vec<DVec2> {
+    let free = (0..adjacency.len())
+        .filter(|node| fixed[*node].is_none())
+        .collect::<Vec<_>>();
+    let index: HashMap<usize, usize> = free
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (*node, index))
+        .collect();
+
+    // each free node's degree times its position, minus its free neighbours'
+    // positions, is the sum of its fixed neighbours' positions
+    let apply = |vector: &[f64]| {
+        free.iter()
+            .map(|node| {
+                adjacency[*node].len() as f64 * vector[index[node]]
+                    - adjacency[*node]
+                        .iter()
+                        .filter_map(|other| index.get(other))
+                        .map(|other| vector[*other])
+                        .sum::<f64>()
+            })
+            .collect::<Vec<_>>()
+    };
+    let pinned = free
+        .iter()
+        .map(|node| {
+            adjacency[*node]
+                .iter()
+                .filter_map(|other| fixed[*other])
+                .sum::<DVec2>()
+        })
+        .collect::<Vec<_>>();
+    let xs = conjugate_gradient(apply, &pinned.iter().map(|v| v.x).collect::<Vec<_>>());
+    let ys = conjugate_gradient(apply, &pinned.iter().map(|v| v.y).collect::<Vec<_>>());
+
+    fixed
+        .iter()
+        .enumerate()
+        .map(|(node, fixed)| {
+            fixed.unwrap_or_else(|| {
+                let index = index[&node];
+                DVec2::new(xs[index], ys[index])
+            })
+        })
+        .collect()
+}
+
+fn conjugate_gradient(apply: impl Fn(&[f64]) -> Vec<f64>, target: &[f64]) -> Vec<f64> {
+    fn dot(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+    let tolerance = 1e-12 * dot(target, target).max(1.);
+    let mut solution = vec![0.; target.len()];
+    let mut residual = target.to_vec();
+    let mut direction = residual.clone();
+    let mut residual_squared = dot(&residual, &residual);
+    for _ in 0..2 * target.len() + 10 {
+        if residual_squared <= tolerance {
+            break;
+        }
+        let applied = apply(&direction);
+        let step = residual_squared / dot(&direction, &applied);
+        for index in 0..target.len() {
+            solution[index] += step * direction[index];
+            residual[index] -= step * applied[index];
+        }
+        let next_squared = dot(&residual, &residual);
+        for index in 0..target.len() {
+            direction[index] = residual[index] + next_squared / residual_squared * direction[index];
+        }
+        residual_squared = next_squared;
+    }
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        difficulty::count_crossings,
+        generate::{GenerateConfig, Generator, PuzzleGenerator},
+    };
+
+    const RADIUS: f32 = 200.;
+
+    fn check_drawing(graph: &StableUnGraph<(), ()>) -> Result<(), String> {
+        let positions = planar_drawing(graph, RADIUS).ok_or("no drawing")?;
+        let drawn = graph.map(|node, _| positions[&node], |_, _| ());
+        let crossings = count_crossings(&drawn);
+        let closest = closest_distance(&drawn.node_weights().copied().collect_vec());
+        if crossings > 0 {
+            return Err(format!("{crossings} crossings"));
+        }
+        if closest < min_spacing(RADIUS, graph.node_count()) {
+            return Err(format!("nodes {closest} apart"));
+        }
+        if let Some(outside) = positions
+            .values()
+            .find(|position| position.length() > RADIUS * 1.001)
+        {
+            return Err(format!("{outside} is outside the circle"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn every_generator_draws_without_crossings() {
+        for generator in Generator::ALL {
+            for num_circles in [3, 5, 9, 14] {
+                let cfg = GenerateConfig {
+                    generator,
+                    min_crossings: 0,
+                    min_difficulty: 0.,
+                    max_difficulty: f32::INFINITY,
+                    num_circles,
+                    limit_nodes: usize::MAX,
+                    node_starting_distance: 100.,
+                    node_starting_random_offset: 20.,
+                };
+                for seed in 0..16 {
+                    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                    let graph = generator.generate(&mut rng, &cfg).unwrap();
+                    if let Err(problem) = check_drawing(&graph) {
+                        panic!("{generator:?} with {num_circles} circles, seed {seed}: {problem}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_drawing_spreads_out_deep_triangulations() {
+        // every node inside the last triangle, which Tutte's embedding squeezes
+        // closer together each time
+        let mut graph = StableUnGraph::<(), ()>::default();
+        let nodes = (0..40).map(|_| graph.add_node(())).collect_vec();
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            graph.add_edge(nodes[a], nodes[b], ());
+        }
+        for node in 3..nodes.len() {
+            for other in node - 3..node {
+                graph.add_edge(nodes[node], nodes[other], ());
+            }
+        }
+        check_drawing(&graph).unwrap();
+    }
+}
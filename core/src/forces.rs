@@ -0,0 +1,142 @@
+use geo::{Closest, ClosestPoint, Line, Point};
+use glam::Vec2;
+use itertools::Itertools;
+
+/// Weights for each vector that add up to 1, with shorter vectors weighted
+/// exponentially more, by `sigma` to the power of minus their length
+pub fn softargmin(vecs: &[Vec2], sigma: f64) -> Vec<(f64, Vec2)> {
+    let mut vecs = vecs
+        .iter()
+        .map(|vec| (sigma.powf(-vec.length() as f64), *vec))
+        .collect_vec();
+    let sum: f64 = vecs.iter().map(|(weight, _)| weight).sum();
+    vecs.iter_mut().for_each(|(weight, _)| {
+        *weight /= sum;
+    });
+    vecs
+}
+
+/// The vectors weighted by [`softargmin`] and added up, so mostly the shortest
+fn weighted_sum(vecs: &[Vec2], base: f64) -> Vec2 {
+    softargmin(vecs, base)
+        .iter()
+        .map(|(weight, vec)| *weight as f32 * *vec)
+        .sum()
+}
+
+/// The pull from `point` towards the nearest of `nodes`
+pub fn points_strength(nodes: impl IntoIterator<Item = Vec2>, point: Vec2, base: f64) -> Vec2 {
+    let nodes = nodes.into_iter().map(|node| node - point).collect_vec();
+    weighted_sum(&nodes, base)
+}
+
+/// The pull from `point` towards the closest point on the nearest of `lines`
+pub fn lines_strength(lines: impl IntoIterator<Item = [Vec2; 2]>, point: Vec2, base: f64) -> Vec2 {
+    fn to_point(v: Vec2) -> Point<f32> {
+        Into::<[f32; 2]>::into(v).into()
+    }
+    fn to_vec(v: Point<f32>) -> Vec2 {
+        Into::<[f32; 2]>::into(v).into()
+    }
+    let geo_point = to_point(point);
+    let lines = lines
+        .into_iter()
+        .map(|[start, end]| {
+            let line = <Line<f32>>::new(to_point(start), to_point(end));
+            match line.closest_point(&geo_point) {
+                Closest::Intersection(closest) | Closest::SinglePoint(closest) => {
+                    to_vec(closest) - point
+                }
+                Closest::Indeterminate => Vec2::ZERO,
+            }
+        })
+        .collect_vec();
+    weighted_sum(&lines, base)
+}
+
+/// The pull from `point` back towards the middle
+pub fn boundary_strength(point: Vec2) -> Vec2 {
+    -point
+}
+
+/// Which way a node at `point` should move to spread the graph out: away from
+/// the nearest node or edge, or back to `target_centre_length` from the middle,
+/// whichever is strongest. It's never more than 3 long.
+pub fn outward_direction(
+    point: Vec2,
+    nodes: impl IntoIterator<Item = Vec2>,
+    lines: impl IntoIterator<Item = [Vec2; 2]>,
+    base: f64,
+    target_centre_length: f32,
+) -> Vec2 {
+    fn invert(value: f32) -> f32 {
+        10. / value.max(1.)
+    }
+    fn with_length(vec: Vec2, length: f32) -> Vec2 {
+        vec.normalize_or_zero() * length
+    }
+    fn distance_to_circle(vec: Vec2, radius: f32) -> f32 {
+        radius - vec.length()
+    }
+    let to_nearest_point = points_strength(nodes, point, base);
+    let to_nearest_line = lines_strength(lines, point, base);
+    let to_centre = boundary_strength(point);
+    [
+        with_length(
+            to_centre,
+            invert(distance_to_circle(to_centre, target_centre_length)),
+        ),
+        with_length(-to_nearest_point, invert(to_nearest_point.length() / 2.)),
+        with_length(-to_nearest_line, invert(to_nearest_line.length())),
+    ]
+    .into_iter()
+    .max_by(|x, y| x.length_squared().total_cmp(&y.length_squared()))
+    .unwrap()
+    .clamp_length_max(3.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn softargmin_favours_short_vectors() {
+        let weights = softargmin(&[Vec2::new(1., 0.), Vec2::new(0., 5.)], 3.);
+        let sum: f64 = weights.iter().map(|(weight, _)| weight).sum();
+        assert!((sum - 1.).abs() < 1e-9);
+        assert!(weights[0].0 > weights[1].0);
+    }
+
+    #[test]
+    fn strengths_pull_towards_the_nearest_thing() {
+        let point = Vec2::new(10., 10.);
+        let nodes = [Vec2::new(12., 10.), Vec2::new(10., 60.)];
+        let to_node = points_strength(nodes, point, 3.);
+        assert!((to_node - Vec2::new(2., 0.)).length() < 0.01);
+
+        // the closest point is partway along the first line
+        let lines = [
+            [Vec2::new(0., 7.), Vec2::new(20., 7.)],
+            [Vec2::new(50., 0.), Vec2::new(50., 20.)],
+        ];
+        let to_line = lines_strength(lines, point, 3.);
+        assert!((to_line - Vec2::new(0., -3.)).length() < 0.01);
+
+        assert_eq!(boundary_strength(point), -point);
+    }
+
+    #[test]
+    fn nodes_spread_out_from_close_neighbours() {
+        // a neighbour just to the right pushes the node left
+        let point = Vec2::new(100., 0.);
+        let direction = outward_direction(point, [Vec2::new(101., 0.)], [], 3., 100.);
+        assert!(direction.x < 0.);
+        assert!(direction.length() <= 3.);
+
+        // with nothing nearby, a node far outside the circle heads back in
+        let point = Vec2::new(1000., 0.);
+        let direction = outward_direction(point, [Vec2::new(-1000., 0.)], [], 3., 100.);
+        assert!(direction.x < 0.);
+        assert!(direction.length() <= 3.);
+    }
+}
@@ -0,0 +1,552 @@
+use std::str::FromStr;
+
+#[cfg(feature = "bevy")]
+use bevy_reflect::Reflect;
+use glam::Vec2;
+use itertools::Itertools;
+use petgraph::{prelude::*, stable_graph::IndexType};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    Rng, RngCore, SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    difficulty::{count_crossings, Difficulty},
+    planarity::is_planar,
+};
+
+pub mod families;
+
+/// The settings that decide what puzzles are generated
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerateConfig {
+    pub generator: Generator,
+    /// Every puzzle starts with at least this many crossings
+    pub min_crossings: u32,
+    /// Puzzles are regenerated until their difficulty score is in this range
+    pub min_difficulty: f32,
+    pub max_difficulty: f32,
+    pub num_circles: usize,
+    pub limit_nodes: usize,
+    pub node_starting_distance: f32,
+    pub node_starting_random_offset: f32,
+}
+
+impl Default for GenerateConfig {
+    fn default() -> Self {
+        Self {
+            generator: Generator::Circles,
+            min_crossings: 1,
+            min_difficulty: 0.,
+            max_difficulty: f32::INFINITY,
+            num_circles: 10,
+            limit_nodes: 6,
+            node_starting_distance: 100.,
+            node_starting_random_offset: 20.,
+        }
+    }
+}
+
+/// A way of making random planar graphs for puzzles
+pub trait PuzzleGenerator {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError>;
+}
+
+/// Which [`PuzzleGenerator`] makes new puzzles
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub enum Generator {
+    #[default]
+    Circles,
+    Delaunay,
+    MaximalPlanar,
+    Grid,
+    Triangular,
+    Outerplanar,
+    TreeWithChords,
+}
+
+impl Generator {
+    pub const ALL: [Self; 7] = [
+        Self::Circles,
+        Self::Delaunay,
+        Self::MaximalPlanar,
+        Self::Grid,
+        Self::Triangular,
+        Self::Outerplanar,
+        Self::TreeWithChords,
+    ];
+
+    /// Look up a generator by the name used in story tags
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "circles" => Self::Circles,
+            "delaunay" => Self::Delaunay,
+            "maximal" => Self::MaximalPlanar,
+            "grid" => Self::Grid,
+            "triangular" => Self::Triangular,
+            "outerplanar" => Self::Outerplanar,
+            "tree" => Self::TreeWithChords,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::from_name(name).ok_or_else(|| format!("unknown generator {name}"))
+    }
+}
+
+impl PuzzleGenerator for Generator {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        match self {
+            Self::Circles => Circles.generate(rng, cfg),
+            Self::Delaunay => families::Delaunay.generate(rng, cfg),
+            Self::MaximalPlanar => families::MaximalPlanar.generate(rng, cfg),
+            Self::Grid => families::Lattice { triangular: false }.generate(rng, cfg),
+            Self::Triangular => families::Lattice { triangular: true }.generate(rng, cfg),
+            Self::Outerplanar => families::Outerplanar.generate(rng, cfg),
+            Self::TreeWithChords => families::TreeWithChords.generate(rng, cfg),
+        }
+    }
+}
+
+/// How many nodes a generator should aim for: as many as the arrangement of
+/// `num_circles` circles has, so sizes mean the same whichever is used
+pub fn node_count(cfg: &GenerateConfig) -> usize {
+    cfg.num_circles * cfg.num_circles.saturating_sub(1) / 2
+}
+
+/// How many puzzles to try when looking for one in the difficulty band
+const MAX_DIFFICULTY_TRIES: usize = 50;
+
+/// How many graphs to try when looking for one that can start tangled enough
+const MAX_TANGLE_TRIES: usize = 20;
+
+/// How many times to shuffle a graph's nodes round to get enough crossings
+const MAX_SHUFFLES: usize = 20;
+
+/// How many graphs to try when looking for a planar one to contract
+const MAX_PLANAR_TRIES: usize = 20;
+
+/// A puzzle before anything is spawned for it, with every node at its starting
+/// position
+pub struct Level {
+    pub graph: StableGraph<Vec2, (), Undirected>,
+    /// The graph before each contraction, biggest first
+    pub previous: Vec<StableGraph<Vec2, (), Undirected>>,
+}
+
+/// Generate levels from a seed until one is in the difficulty band
+pub fn pick_level(seed: u64, cfg: &GenerateConfig) -> Result<(Level, Difficulty), GenerateError> {
+    // unlike `StdRng`, this gives the same numbers on every platform and version
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    // keep whichever level comes closest to the band, in case none are in it
+    let mut best: Option<(f32, Difficulty, Level)> = None;
+    for _ in 0..MAX_DIFFICULTY_TRIES {
+        let level = make_level(&mut rng, cfg)?;
+        let level_difficulty = Difficulty::of(&level.graph);
+        let score = level_difficulty.score;
+        let distance = (cfg.min_difficulty - score)
+            .max(score - cfg.max_difficulty)
+            .max(0.);
+        if !best
+            .as_ref()
+            .is_some_and(|(best_distance, ..)| *best_distance <= distance)
+        {
+            best = Some((distance, level_difficulty, level));
+        }
+        if distance == 0. {
+            break;
+        }
+    }
+    let (_, difficulty, level) = best.expect("at least one level is tried");
+    Ok((level, difficulty))
+}
+
+impl Level {
+    /// A level that can't be made any bigger, with its nodes placed like a
+    /// generated one's
+    pub fn scattered<N, E>(
+        graph: &StableGraph<N, E, Undirected>,
+        rng: &mut impl Rng,
+        cfg: &GenerateConfig,
+    ) -> Self {
+        Self {
+            graph: graph.map(|_, _| starting_position(rng, cfg), |_, _| ()),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Shuffle the nodes round until they have at least `min_crossings`
+    /// crossings, returning whether that worked
+    pub fn tangle(&mut self, rng: &mut impl Rng, min_crossings: u32) -> bool {
+        for _ in 0..MAX_SHUFFLES {
+            if count_crossings(&self.graph) >= min_crossings as usize {
+                return true;
+            }
+            self.shuffle(rng);
+        }
+        false
+    }
+
+    /// Swap the starting positions of the graph's nodes round at random
+    fn shuffle(&mut self, rng: &mut impl Rng) {
+        let nodes = self.graph.node_indices().collect_vec();
+        let mut positions = nodes.iter().map(|node| self.graph[*node]).collect_vec();
+        positions.shuffle(rng);
+        for (node, position) in nodes.into_iter().zip(positions) {
+            self.graph[node] = position;
+            for graph in &mut self.previous {
+                graph[node] = position;
+            }
+        }
+    }
+}
+
+/// Make a level that starts with at least `cfg.min_crossings` crossings, by
+/// shuffling its nodes round, or starting again if that doesn't work
+fn make_level(rng: &mut ChaCha8Rng, cfg: &GenerateConfig) -> Result<Level, GenerateError> {
+    for _ in 0..MAX_TANGLE_TRIES {
+        let mut level = contract_level(rng, cfg)?;
+        if level.tangle(rng, cfg.min_crossings) {
+            return Ok(level);
+        }
+    }
+    Err(GenerateError::Untangled {
+        min_crossings: cfg.min_crossings,
+        tries: MAX_TANGLE_TRIES,
+    })
+}
+
+/// Generate a planar graph, lay it out, then contract it down to the node limit
+fn contract_level(rng: &mut ChaCha8Rng, cfg: &GenerateConfig) -> Result<Level, GenerateError> {
+    let mut planar = None;
+    for _ in 0..MAX_PLANAR_TRIES {
+        let mut graph = cfg.generator.generate(rng, cfg)?;
+        strip_leaves(&mut graph);
+        // contracting edges keeps a graph planar, so every level after this is
+        // solvable too
+        if is_planar(&graph) {
+            planar = Some(graph);
+            break;
+        }
+    }
+    let graph = planar.ok_or(GenerateError::NonPlanar {
+        tries: MAX_PLANAR_TRIES,
+    })?;
+
+    let mut graph = graph.map(|_, _| starting_position(rng, cfg), |_, _| ());
+
+    let mut previous = Vec::with_capacity(graph.node_count().saturating_sub(cfg.limit_nodes));
+
+    /// One of `nodes` with an edge, more likely the more edges it has
+    fn degree_weighted_random<N>(
+        graph: &StableGraph<N, (), Undirected>,
+        rng: &mut impl Rng,
+        nodes: impl Iterator<Item = NodeIndex>,
+    ) -> Result<NodeIndex, GenerateError> {
+        let (nodes, degrees): (Vec<_>, Vec<_>) = nodes
+            .map(|node| (node, graph.neighbors(node).count()))
+            .filter(|(_, degree)| *degree > 0)
+            .unzip();
+        match WeightedIndex::new(degrees.iter().map(|degree| degree.ilog2())) {
+            Ok(distribution) => Ok(nodes[distribution.sample(rng)]),
+            // every node is a leaf, so none is any more likely than the others
+            Err(_) => nodes.choose(rng).copied().ok_or(GenerateError::Edgeless {
+                nodes: graph.node_count(),
+            }),
+        }
+    }
+
+    while cfg.limit_nodes < graph.node_count() {
+        previous.push(graph.clone());
+        let node = degree_weighted_random(&graph, rng, graph.node_indices())?;
+        let other = degree_weighted_random(&graph, rng, graph.neighbors(node))?;
+        merge_nodes(&mut graph, node, other);
+    }
+    debug_assert!(is_planar(&graph));
+    Ok(Level { graph, previous })
+}
+
+/// Take away every node with fewer than two edges, then any that are left with
+/// fewer than two, until none are
+fn strip_leaves(graph: &mut StableUnGraph<(), ()>) {
+    let mut leaves = graph
+        .node_indices()
+        .filter(|node| graph.neighbors(*node).count() < 2)
+        .collect_vec();
+    while let Some(leaf) = leaves.pop() {
+        if !graph.contains_node(leaf) {
+            continue;
+        }
+        let neighbours = graph.neighbors(leaf).collect_vec();
+        graph.remove_node(leaf);
+        leaves.extend(
+            neighbours
+                .into_iter()
+                .filter(|node| graph.neighbors(*node).count() < 2),
+        );
+    }
+}
+
+/// Somewhere round the ring new nodes start on
+fn starting_position(rng: &mut impl Rng, cfg: &GenerateConfig) -> Vec2 {
+    let random_offset = cfg.node_starting_random_offset;
+    random_direction(rng)
+        * if random_offset != 0. {
+            cfg.node_starting_distance + rng.gen_range((-random_offset)..random_offset)
+        } else {
+            cfg.node_starting_distance
+        }
+}
+
+/// A uniformly random unit vector, found without trigonometry so that it comes
+/// out the same on every platform
+fn random_direction(rng: &mut impl Rng) -> Vec2 {
+    loop {
+        let vec = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+        let length_squared = vec.length_squared();
+        if 1e-6 < length_squared && length_squared <= 1. {
+            return vec / length_squared.sqrt();
+        }
+    }
+}
+
+/// How many circles can be thrown away for being in a degenerate position
+/// before giving up on a graph
+const MAX_RESAMPLES: usize = 1000;
+
+/// How close to degenerate circles can be, as an area in the unit square their
+/// centres are picked from
+const GENERAL_POSITION_TOLERANCE: f32 = 1e-4;
+
+#[derive(Error, Debug, Clone)]
+pub enum GenerateError {
+    #[error("Couldn't place circle {placed} of {wanted} in general position after {tries} tries")]
+    Degenerate {
+        placed: usize,
+        wanted: usize,
+        tries: usize,
+    },
+    #[error("Couldn't lay out a graph with {min_crossings} crossings after {tries} tries")]
+    Untangled { min_crossings: u32, tries: usize },
+    #[error("Contracting a graph left {nodes} nodes with no edges between them")]
+    Edgeless { nodes: usize },
+    #[error("Couldn't generate a planar graph after {tries} tries")]
+    NonPlanar { tries: usize },
+}
+
+/// Arrangements of circles through the origin, as in [`make_graph`]
+#[derive(Debug, Clone, Copy)]
+pub struct Circles;
+
+impl PuzzleGenerator for Circles {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        make_graph(rng, cfg.num_circles)
+    }
+}
+
+/// The graph of an arrangement of random circles that all pass through the
+/// origin, with a node wherever two circles meet away from the origin and
+/// edges along the arcs between them
+fn make_graph(
+    mut rng: impl Rng,
+    num_circles: usize,
+) -> Result<StableUnGraph<(), ()>, GenerateError> {
+    fn sort_pair(x: usize, y: usize) -> (usize, usize) {
+        (x.min(y), x.max(y))
+    }
+    // every pair of circles meets once, and each circle's meetings are joined
+    // up in a path
+    let num_nodes = num_circles * num_circles.saturating_sub(1) / 2;
+    let num_edges = num_circles * num_circles.saturating_sub(2);
+
+    let mut circles: Vec<Vec2> = Vec::with_capacity(num_circles);
+    let mut resamples = 0;
+    while circles.len() < num_circles {
+        let circle = rng.gen::<[f32; 2]>().into();
+        if in_general_position(&circles, circle) {
+            circles.push(circle);
+        } else if resamples < MAX_RESAMPLES {
+            resamples += 1;
+        } else {
+            return Err(GenerateError::Degenerate {
+                placed: circles.len(),
+                wanted: num_circles,
+                tries: resamples,
+            });
+        }
+    }
+
+    let mut graph = UnGraphMap::with_capacity(num_nodes, num_edges);
+    // for each circle-circle pair, connect it to each of its neighbours when
+    // sorting each circle's intersections by how far round the circle they are;
+    // that is, connect intersections that are connected by an arc if there's no
+    // other intersection on that arc, including 0,0 the distance along each
+    // circle with midpoint A, of circle with midpoint B is proportional to
+    // angle OAB, so that's a proxy for sorting
+    for (i, circle) in circles.iter().enumerate() {
+        let arc_dist = |other_centre| half_turn_pseudoangle(*circle, other_centre - *circle);
+        let mut others = circles
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .collect::<Vec<_>>();
+        // general position means no two of these are equal
+        others.sort_by(|(_, &x), (_, &y)| arc_dist(x).total_cmp(&arc_dist(y)));
+        // add adjacent intersections between circle i and other circles
+        // to the graph as edges
+        for [(a, _), (b, _)] in others.array_windows() {
+            graph.add_edge(sort_pair(*a, i), sort_pair(*b, i), ());
+        }
+    }
+    debug_assert_eq!(graph.edge_count(), num_edges);
+    Ok(graph
+        .into_graph()
+        .map(
+            |_node_index, _node_weight| (),
+            |_edge_index, _edge_weight| (),
+        )
+        .into())
+}
+
+/// Whether a new circle can join the arrangement without degeneracies. All the
+/// circles go through the origin, so two are tangent when their centres line up
+/// with the origin, and three meet at a single point, giving coincident
+/// intersections, when their centres line up with each other.
+fn in_general_position(circles: &[Vec2], new: Vec2) -> bool {
+    let tolerance = GENERAL_POSITION_TOLERANCE;
+    new.length() > tolerance
+        && circles
+            .iter()
+            .all(|circle| circle.perp_dot(new).abs() > tolerance)
+        && circles
+            .iter()
+            .tuple_combinations()
+            .all(|(a, b)| (*b - *a).perp_dot(new - *a).abs() > tolerance)
+}
+
+/// Increases with the angle from `from` to `to`, modulo half a turn, like
+/// `from.angle_between(to).rem_euclid(PI)` but without trigonometry, so that
+/// sorting by it gives the same order on every platform
+fn half_turn_pseudoangle(from: Vec2, to: Vec2) -> f32 {
+    let (x, y) = (from.dot(to), from.perp_dot(to));
+    let sum = x.abs() + y.abs();
+    if sum == 0. {
+        return 0.;
+    }
+    // ranges over [0, 4) for a full turn
+    let pseudoangle = if y >= 0. { 1. - x / sum } else { 3. + x / sum };
+    pseudoangle.rem_euclid(2.)
+}
+
+fn merge_nodes<N, E, Ix: IndexType>(
+    graph: &mut StableGraph<N, E, Undirected, Ix>,
+    target: NodeIndex<Ix>,
+    other: NodeIndex<Ix>,
+) {
+    while let Some((edge_index, node_index)) = graph.neighbors(other).detach().next(graph) {
+        let edge_weight = graph.remove_edge(edge_index).unwrap();
+        if target == node_index {
+            continue;
+        }
+        graph.add_edge(node_index, target, edge_weight);
+    }
+    graph.remove_node(other).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(generator: Generator, num_circles: usize, limit_nodes: usize) -> GenerateConfig {
+        GenerateConfig {
+            generator,
+            min_crossings: 0,
+            min_difficulty: 0.,
+            max_difficulty: f32::INFINITY,
+            num_circles,
+            limit_nodes,
+            node_starting_distance: 100.,
+            node_starting_random_offset: 20.,
+        }
+    }
+
+    #[test]
+    fn every_generator_makes_levels_for_many_seeds() {
+        for generator in Generator::ALL {
+            for (num_circles, limit_nodes) in [(2, 2), (5, 3), (7, 3), (9, 2), (10, 6)] {
+                let cfg = config(generator, num_circles, limit_nodes);
+                for seed in 0..64 {
+                    let (level, _) = pick_level(seed, &cfg).unwrap_or_else(|error| {
+                        panic!("{generator:?} {num_circles}/{limit_nodes} seed {seed}: {error}")
+                    });
+                    assert!(level.graph.node_count() <= limit_nodes);
+                    assert!(is_planar(&level.graph));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn levels_only_depend_on_the_seed() {
+        let cfg = GenerateConfig {
+            min_crossings: 1,
+            ..config(Generator::Circles, 10, 6)
+        };
+        let positions = |seed| {
+            let (level, _) = pick_level(seed, &cfg).unwrap();
+            level.graph.node_weights().copied().collect_vec()
+        };
+        assert_eq!(positions(7), positions(7));
+        assert_ne!(positions(7), positions(8));
+    }
+
+    #[test]
+    fn tangled_levels_have_enough_crossings() {
+        for generator in Generator::ALL {
+            let cfg = GenerateConfig {
+                min_crossings: 2,
+                ..config(generator, 10, 8)
+            };
+            let (level, difficulty) = pick_level(1, &cfg).unwrap();
+            assert!(count_crossings(&level.graph) >= 2, "{generator:?}");
+            assert_eq!(difficulty.crossings as usize, count_crossings(&level.graph));
+        }
+    }
+
+    #[test]
+    fn stripping_leaves_keeps_cycles() {
+        // a triangle with a path hanging off it, and a node on its own
+        let mut graph = StableUnGraph::<(), ()>::default();
+        let nodes = (0..6).map(|_| graph.add_node(())).collect_vec();
+        for (a, b) in [(0, 1), (1, 2), (2, 0), (2, 3), (3, 4)] {
+            graph.add_edge(nodes[a], nodes[b], ());
+        }
+        strip_leaves(&mut graph);
+        assert_eq!(graph.node_indices().collect_vec(), nodes[..3]);
+        assert_eq!(graph.edge_count(), 3);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeSet;
+
+use glam::DVec2;
+use petgraph::prelude::*;
+use rand::{seq::SliceRandom, Rng, RngCore};
+
+use crate::{
+    generate::{node_count, GenerateConfig, GenerateError, PuzzleGenerator},
+    planarity::is_planar,
+};
+
+/// How likely each edge of a Delaunay triangulation is to be deleted
+const DELAUNAY_DELETION_CHANCE: f64 = 0.25;
+
+/// How likely each chord of an outerplanar graph is to be kept
+const OUTERPLANAR_CHORD_CHANCE: f64 = 0.7;
+
+/// Random points joined up by their Delaunay triangulation, with some edges
+/// taken out
+#[derive(Debug, Clone, Copy)]
+pub struct Delaunay;
+
+impl PuzzleGenerator for Delaunay {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        let points = (0..node_count(cfg))
+            .map(|_| DVec2::new(rng.gen(), rng.gen()))
+            .collect::<Vec<_>>();
+        let mut edges = triangle_edges(&delaunay_triangles(&points));
+        let mut degrees = degrees(points.len(), &edges);
+        // leave every node with enough edges that it won't be thrown away
+        edges.retain(|&(a, b)| {
+            let delete = degrees[a] > 2 && degrees[b] > 2 && rng.gen_bool(DELAUNAY_DELETION_CHANCE);
+            if delete {
+                degrees[a] -= 1;
+                degrees[b] -= 1;
+            }
+            !delete
+        });
+        Ok(from_edges(points.len(), edges))
+    }
+}
+
+/// Triangles of the Delaunay triangulation of some points, by the
+/// Bowyer-Watson algorithm
+fn delaunay_triangles(points: &[DVec2]) -> Vec<[usize; 3]> {
+    // a triangle round every point, whose corners are removed at the end
+    let count = points.len();
+    let mut all_points = points.to_vec();
+    all_points.extend([
+        DVec2::new(-10., -10.),
+        DVec2::new(20., -10.),
+        DVec2::new(-10., 20.),
+    ]);
+    let mut triangles = vec![[count, count + 1, count + 2]];
+    for (index, point) in points.iter().enumerate() {
+        let (bad, good): (Vec<_>, Vec<_>) = triangles.into_iter().partition(|triangle| {
+            in_circumcircle(triangle.map(|corner| all_points[corner]), *point)
+        });
+        triangles = good;
+        // the edges round the hole left by the bad triangles
+        let sides = bad
+            .iter()
+            .flat_map(|[a, b, c]| [(*a, *b), (*b, *c), (*c, *a)])
+            .collect::<Vec<_>>();
+        for &(a, b) in &sides {
+            if !sides.contains(&(b, a)) {
+                triangles.push([a, b, index]);
+            }
+        }
+    }
+    triangles.retain(|triangle| triangle.iter().all(|corner| *corner < count));
+    triangles
+}
+
+/// Whether a point is strictly inside the circle through a triangle's corners
+fn in_circumcircle([a, b, c]: [DVec2; 3], point: DVec2) -> bool {
+    let orientation = (b - a).perp_dot(c - a);
+    let (a, b, c) = (a - point, b - point, c - point);
+    let determinant = a.length_squared() * b.perp_dot(c)
+        + b.length_squared() * c.perp_dot(a)
+        + c.length_squared() * a.perp_dot(b);
+    determinant * orientation.signum() > 0.
+}
+
+/// Maximal planar graphs, made by adding nodes inside random faces of a
+/// triangle and then flipping random edges
+#[derive(Debug, Clone, Copy)]
+pub struct MaximalPlanar;
+
+impl PuzzleGenerator for MaximalPlanar {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        let count = node_count(cfg).max(3);
+        let mut faces = vec![[0, 1, 2], [0, 1, 2]];
+        for node in 3..count {
+            let face = rng.gen_range(0..faces.len());
+            let [a, b, c] = faces[face];
+            faces[face] = [a, b, node];
+            faces.extend([[b, c, node], [c, a, node]]);
+        }
+        let mut edges = triangle_edges(&faces);
+
+        // stacking nodes in faces alone gives a very particular kind of graph
+        for _ in 0..2 * count {
+            let &(a, b) = edges.iter().nth(rng.gen_range(0..edges.len())).unwrap();
+            let sides = faces
+                .iter()
+                .enumerate()
+                .filter(|(_, face)| face.contains(&a) && face.contains(&b))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            let &[first, second] = &sides[..] else {
+                continue;
+            };
+            let opposite =
+                |face: [usize; 3]| *face.iter().find(|node| **node != a && **node != b).unwrap();
+            let (c, d) = (opposite(faces[first]), opposite(faces[second]));
+            let degrees = degrees(count, &edges);
+            if c == d || edges.contains(&sort_pair(c, d)) || degrees[a] <= 3 || degrees[b] <= 3 {
+                continue;
+            }
+            edges.remove(&(a, b));
+            edges.insert(sort_pair(c, d));
+            faces[first] = [a, c, d];
+            faces[second] = [b, c, d];
+        }
+        Ok(from_edges(count, edges))
+    }
+}
+
+/// Nodes in a square grid, optionally with a diagonal across each square
+#[derive(Debug, Clone, Copy)]
+pub struct Lattice {
+    pub triangular: bool,
+}
+
+impl PuzzleGenerator for Lattice {
+    fn generate(
+        &self,
+        _rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        let side = (node_count(cfg) as f64).sqrt().ceil().max(2.) as usize;
+        let index = |row: usize, column: usize| row * side + column;
+        let mut edges = BTreeSet::new();
+        for row in 0..side {
+            for column in 0..side {
+                if column + 1 < side {
+                    edges.insert((index(row, column), index(row, column + 1)));
+                }
+                if row + 1 < side {
+                    edges.insert((index(row, column), index(row + 1, column)));
+                }
+                if self.triangular && row + 1 < side && column + 1 < side {
+                    edges.insert((index(row, column), index(row + 1, column + 1)));
+                }
+            }
+        }
+        Ok(from_edges(side * side, edges))
+    }
+}
+
+/// A cycle with random chords that don't cross inside it
+#[derive(Debug, Clone, Copy)]
+pub struct Outerplanar;
+
+impl PuzzleGenerator for Outerplanar {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        let count = node_count(cfg).max(3);
+        let mut edges = (0..count)
+            .map(|node| sort_pair(node, (node + 1) % count))
+            .collect::<BTreeSet<_>>();
+        // split the polygon along random diagonals until it's all triangles
+        let mut polygons = vec![(0..count).collect::<Vec<_>>()];
+        while let Some(polygon) = polygons.pop() {
+            let len = polygon.len();
+            if len <= 3 {
+                continue;
+            }
+            let start = rng.gen_range(0..len - 2);
+            let end = rng.gen_range(start + 2..if start == 0 { len - 1 } else { len });
+            if rng.gen_bool(OUTERPLANAR_CHORD_CHANCE) {
+                edges.insert(sort_pair(polygon[start], polygon[end]));
+            }
+            polygons.push(polygon[start..=end].to_vec());
+            polygons.push(
+                polygon[end..]
+                    .iter()
+                    .chain(&polygon[..=start])
+                    .copied()
+                    .collect(),
+            );
+        }
+        Ok(from_edges(count, edges))
+    }
+}
+
+/// A random tree with extra edges added wherever they keep it planar
+#[derive(Debug, Clone, Copy)]
+pub struct TreeWithChords;
+
+impl PuzzleGenerator for TreeWithChords {
+    fn generate(
+        &self,
+        rng: &mut dyn RngCore,
+        cfg: &GenerateConfig,
+    ) -> Result<StableUnGraph<(), ()>, GenerateError> {
+        let count = node_count(cfg).max(2);
+        let mut graph = StableUnGraph::with_capacity(count, 2 * count);
+        let nodes = (0..count).map(|_| graph.add_node(())).collect::<Vec<_>>();
+        for node in 1..count {
+            let parent = rng.gen_range(0..node);
+            graph.add_edge(nodes[parent], nodes[node], ());
+        }
+        // enough tries that most nodes end up in a cycle
+        for _ in 0..2 * count {
+            let mut pair = nodes.choose_multiple(rng, 2);
+            let (a, b) = (*pair.next().unwrap(), *pair.next().unwrap());
+            if graph.find_edge(a, b).is_some() {
+                continue;
+            }
+            let edge = graph.add_edge(a, b, ());
+            if !is_planar(&graph) {
+                graph.remove_edge(edge);
+            }
+        }
+        Ok(graph)
+    }
+}
+
+fn sort_pair(x: usize, y: usize) -> (usize, usize) {
+    (x.min(y), x.max(y))
+}
+
+fn triangle_edges(triangles: &[[usize; 3]]) -> BTreeSet<(usize, usize)> {
+    triangles
+        .iter()
+        .flat_map(|[a, b, c]| [sort_pair(*a, *b), sort_pair(*b, *c), sort_pair(*c, *a)])
+        .collect()
+}
+
+fn degrees<'a>(count: usize, edges: impl IntoIterator<Item = &'a (usize, usize)>) -> Vec<usize> {
+    let mut degrees = vec![0; count];
+    for (a, b) in edges {
+        degrees[*a] += 1;
+        degrees[*b] += 1;
+    }
+    degrees
+}
+
+fn from_edges(
+    count: usize,
+    edges: impl IntoIterator<Item = (usize, usize)>,
+) -> StableUnGraph<(), ()> {
+    let mut graph = StableUnGraph::default();
+    let nodes = (0..count).map(|_| graph.add_node(())).collect::<Vec<_>>();
+    for (a, b) in edges {
+        graph.add_edge(nodes[a], nodes[b], ());
+    }
+    graph
+}
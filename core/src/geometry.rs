@@ -0,0 +1,194 @@
+use geo::{
+    kernels::{Kernel, Orientation, RobustKernel},
+    Coord,
+};
+use glam::{DMat2, Vec2};
+
+/// The ways two segments can meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentIntersection {
+    /// The segments have no point in common
+    Disjoint,
+    /// The interiors of the segments cross at a single point
+    Proper,
+    /// The segments only meet at an endpoint of both
+    Touching,
+    /// The segments lie along the same line and share more than a point
+    CollinearOverlap,
+    /// An endpoint of one segment lies in the interior of the other
+    VertexOnEdge,
+}
+
+/// Classify how two segments meet, using exact orientation tests so that the
+/// answer doesn't depend on rounding
+pub fn segment_intersection([p1, p2]: [Vec2; 2], [q1, q2]: [Vec2; 2]) -> SegmentIntersection {
+    use Orientation::Collinear;
+    use SegmentIntersection::*;
+
+    let [p1, p2, q1, q2] = [p1, p2, q1, q2].map(coord);
+    match (p1 == p2, q1 == q2) {
+        (true, true) if p1 == q1 => return Touching,
+        (true, true) => return Disjoint,
+        (true, false) => return point_on_segment(p1, [q1, q2]),
+        (false, true) => return point_on_segment(q1, [p1, p2]),
+        (false, false) => {}
+    }
+
+    let orient = RobustKernel::orient2d;
+    let [d1, d2, d3, d4] = [
+        orient(p1, p2, q1),
+        orient(p1, p2, q2),
+        orient(q1, q2, p1),
+        orient(q1, q2, p2),
+    ];
+
+    if d1 == Collinear && d2 == Collinear {
+        return collinear_overlap([p1, p2], [q1, q2]);
+    }
+    if (d1 != Collinear && d1 == d2) || (d3 != Collinear && d3 == d4) {
+        return Disjoint;
+    }
+    if [d1, d2, d3, d4].iter().all(|d| *d != Collinear) {
+        return Proper;
+    }
+    // segments that aren't collinear can only meet at one point, so a shared
+    // endpoint is the only place they touch
+    if [p1, p2].iter().any(|p| [q1, q2].contains(p)) {
+        return Touching;
+    }
+    let vertex_on_edge = (d1 == Collinear && within(q1, [p1, p2]))
+        || (d2 == Collinear && within(q2, [p1, p2]))
+        || (d3 == Collinear && within(p1, [q1, q2]))
+        || (d4 == Collinear && within(p2, [q1, q2]));
+    if vertex_on_edge {
+        VertexOnEdge
+    } else {
+        Disjoint
+    }
+}
+
+fn coord(v: Vec2) -> Coord<f64> {
+    Into::<[f64; 2]>::into(v.as_dvec2()).into()
+}
+
+/// Whether a point already known to be on the line through a segment lies on
+/// the segment itself
+fn within(point: Coord<f64>, [start, end]: [Coord<f64>; 2]) -> bool {
+    start.x.min(end.x) <= point.x
+        && point.x <= start.x.max(end.x)
+        && start.y.min(end.y) <= point.y
+        && point.y <= start.y.max(end.y)
+}
+
+fn point_on_segment(point: Coord<f64>, [start, end]: [Coord<f64>; 2]) -> SegmentIntersection {
+    if point == start || point == end {
+        SegmentIntersection::Touching
+    } else if RobustKernel::orient2d(start, end, point) == Orientation::Collinear
+        && within(point, [start, end])
+    {
+        SegmentIntersection::VertexOnEdge
+    } else {
+        SegmentIntersection::Disjoint
+    }
+}
+
+fn collinear_overlap(p: [Coord<f64>; 2], q: [Coord<f64>; 2]) -> SegmentIntersection {
+    // points along a line are ordered the same way as their coordinates
+    fn sorted([a, b]: [Coord<f64>; 2]) -> [(f64, f64); 2] {
+        let (a, b) = (a.x_y(), b.x_y());
+        if a <= b {
+            [a, b]
+        } else {
+            [b, a]
+        }
+    }
+    let [p_low, p_high] = sorted(p);
+    let [q_low, q_high] = sorted(q);
+    let low = if p_low >= q_low { p_low } else { q_low };
+    let high = if p_high <= q_high { p_high } else { q_high };
+    if low > high {
+        SegmentIntersection::Disjoint
+    } else if low == high {
+        SegmentIntersection::Touching
+    } else {
+        SegmentIntersection::CollinearOverlap
+    }
+}
+
+/// How far along `a_vec` and `b_vec` to go from two points `starting_difference`
+/// apart for them to meet, or `None` if the vectors are parallel
+pub fn intersection_scalars(a_vec: Vec2, b_vec: Vec2, starting_difference: Vec2) -> Option<Vec2> {
+    let matrix = DMat2::from_cols(a_vec.as_dvec2(), b_vec.as_dvec2());
+    let determinant = matrix.determinant();
+    if determinant == 0. {
+        return None;
+    }
+    let inverted = matrix.inverse();
+    // vec of [a_coefficient, b_coefficient] to roughly add to difference
+    Some((inverted * starting_difference.as_dvec2()).as_vec2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SegmentIntersection::*;
+
+    fn meet(p: [[f32; 2]; 2], q: [[f32; 2]; 2]) -> SegmentIntersection {
+        let line = |[a, b]: [[f32; 2]; 2]| [Vec2::from(a), Vec2::from(b)];
+        let result = segment_intersection(line(p), line(q));
+        // the answer doesn't depend on the order of the segments or their ends
+        assert_eq!(segment_intersection(line(q), line(p)), result);
+        assert_eq!(segment_intersection(line([p[1], p[0]]), line(q)), result);
+        result
+    }
+
+    #[test]
+    fn classifies_each_kind_of_meeting() {
+        assert_eq!(meet([[0., 0.], [2., 2.]], [[0., 2.], [2., 0.]]), Proper);
+        assert_eq!(meet([[0., 0.], [1., 0.]], [[0., 1.], [1., 1.]]), Disjoint);
+        assert_eq!(meet([[0., 0.], [1., 0.]], [[1., 0.], [1., 1.]]), Touching);
+        assert_eq!(
+            meet([[0., 0.], [2., 0.]], [[1., 0.], [1., 1.]]),
+            VertexOnEdge
+        );
+        assert_eq!(
+            meet([[0., 0.], [2., 0.]], [[1., 0.], [3., 0.]]),
+            CollinearOverlap
+        );
+        assert_eq!(meet([[0., 0.], [1., 0.]], [[1., 0.], [3., 0.]]), Touching);
+        assert_eq!(meet([[0., 0.], [1., 0.]], [[2., 0.], [3., 0.]]), Disjoint);
+        // the line through one segment crosses the other, but not the segment
+        assert_eq!(meet([[0., 0.], [1., 1.]], [[3., 0.], [2., 5.]]), Disjoint);
+    }
+
+    #[test]
+    fn handles_segments_of_no_length() {
+        assert_eq!(meet([[1., 1.], [1., 1.]], [[1., 1.], [1., 1.]]), Touching);
+        assert_eq!(meet([[1., 1.], [1., 1.]], [[2., 2.], [2., 2.]]), Disjoint);
+        assert_eq!(
+            meet([[1., 1.], [1., 1.]], [[0., 0.], [2., 2.]]),
+            VertexOnEdge
+        );
+        assert_eq!(meet([[0., 0.], [0., 0.]], [[0., 0.], [2., 2.]]), Touching);
+        assert_eq!(meet([[1., 0.], [1., 0.]], [[0., 0.], [2., 2.]]), Disjoint);
+    }
+
+    #[test]
+    fn nearly_parallel_segments_are_exact() {
+        // a point a single rounding step off the line through the other segment
+        let off = 1. + f32::EPSILON;
+        assert_eq!(meet([[0., 0.], [3., 3.]], [[1., off], [5., 7.]]), Disjoint);
+        assert_eq!(
+            meet([[0., 0.], [3., 3.]], [[1., 1.], [5., 7.]]),
+            VertexOnEdge
+        );
+    }
+
+    #[test]
+    fn scalars_find_where_lines_meet() {
+        let (a, b) = (Vec2::new(1., 0.), Vec2::new(0., 1.));
+        let scalars = intersection_scalars(a, b, Vec2::new(2., 3.)).unwrap();
+        assert!((scalars - Vec2::new(2., 3.)).length() < 1e-6);
+        assert_eq!(intersection_scalars(a, a * 2., Vec2::ONE), None);
+    }
+}
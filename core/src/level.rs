@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use glam::Vec2;
+use itertools::Itertools;
+use petgraph::{prelude::*, visit::IntoEdgeReferences};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    difficulty::{count_crossings, Difficulty},
+    drawing::planar_drawing,
+    generate::{pick_level, GenerateConfig, GenerateError},
+    planarity::is_planar,
+};
+
+/// A puzzle as it's written in a `.level` file in RON
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LevelFile {
+    pub title: String,
+    /// How many moves it should take to untangle
+    #[serde(default)]
+    pub par_moves: Option<u32>,
+    pub vertices: Vec<LevelVertex>,
+    /// Pairs of indices into `vertices`
+    pub edges: Vec<(usize, usize)>,
+    /// Where each vertex goes when the puzzle is solved
+    #[serde(default)]
+    pub solution: Option<Vec<[f32; 2]>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LevelVertex {
+    pub position: [f32; 2],
+    /// Pinned vertices can't be moved by the player
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum LevelFileError {
+    #[error("Could not parse level: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("Edge from {start} to {end} needs a vertex that isn't in the {count} given")]
+    MissingVertex {
+        start: usize,
+        end: usize,
+        count: usize,
+    },
+    #[error("Solution has {solution} positions for {count} vertices")]
+    SolutionLength { solution: usize, count: usize },
+}
+
+impl LevelFile {
+    /// Read a level from RON, checking it makes sense
+    pub fn parse(bytes: &[u8]) -> Result<Self, LevelFileError> {
+        let level: Self = ron::de::from_bytes(bytes)?;
+        level.validate()?;
+        Ok(level)
+    }
+
+    fn validate(&self) -> Result<(), LevelFileError> {
+        let count = self.vertices.len();
+        if let Some(&(start, end)) = self
+            .edges
+            .iter()
+            .find(|(start, end)| *start >= count || *end >= count)
+        {
+            return Err(LevelFileError::MissingVertex { start, end, count });
+        }
+        match &self.solution {
+            Some(solution) if solution.len() != count => Err(LevelFileError::SolutionLength {
+                solution: solution.len(),
+                count,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Generate a puzzle from a seed as a level, with its solution drawn in a
+    /// circle of the given radius
+    pub fn generate(seed: u64, cfg: &GenerateConfig, radius: f32) -> Result<Self, GenerateError> {
+        let (level, _) = pick_level(seed, cfg)?;
+        let graph = &level.graph;
+        let indices: HashMap<NodeIndex, usize> = graph
+            .node_indices()
+            .enumerate()
+            .map(|(index, node)| (node, index))
+            .collect();
+        let solution = planar_drawing(graph, radius).and_then(|positions| {
+            graph
+                .node_indices()
+                .map(|node| positions.get(&node).map(|position| (*position).into()))
+                .collect()
+        });
+        Ok(Self {
+            title: format!("Puzzle {seed}"),
+            par_moves: None,
+            vertices: graph
+                .node_indices()
+                .map(|node| LevelVertex {
+                    position: graph[node].into(),
+                    pinned: false,
+                })
+                .collect(),
+            edges: graph
+                .edge_references()
+                .map(|edge| (indices[&edge.source()], indices[&edge.target()]))
+                .collect(),
+            solution,
+        })
+    }
+
+    /// The level's graph with each node where it starts
+    pub fn graph(&self) -> StableGraph<Vec2, (), Undirected> {
+        self.graph_at(self.vertices.iter().map(|vertex| vertex.position))
+    }
+
+    /// How many crossings the level has in its starting layout
+    pub fn layout_crossings(&self) -> usize {
+        count_crossings(&self.graph())
+    }
+
+    /// How many crossings the level's solution has, if it has one
+    pub fn solution_crossings(&self) -> Option<usize> {
+        let solution = self.solution.as_ref()?;
+        Some(count_crossings(&self.graph_at(solution.iter().copied())))
+    }
+
+    fn graph_at(
+        &self,
+        positions: impl IntoIterator<Item = [f32; 2]>,
+    ) -> StableGraph<Vec2, (), Undirected> {
+        let mut graph = StableGraph::default();
+        let nodes = positions
+            .into_iter()
+            .map(|position| graph.add_node(Vec2::from(position)))
+            .collect_vec();
+        for &(start, end) in &self.edges {
+            graph.add_edge(nodes[start], nodes[end], ());
+        }
+        graph
+    }
+}
+
+/// Numbers describing a level, for picking which puzzles to keep
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub nodes: usize,
+    pub edges: usize,
+    /// How many nodes have each degree, starting from degree 0
+    pub degree_histogram: Vec<usize>,
+    /// Whether it can be solved at all
+    pub planar: bool,
+    /// Includes the crossings in the starting layout
+    pub difficulty: Difficulty,
+}
+
+impl Stats {
+    pub fn of(level: &LevelFile) -> Self {
+        let graph = level.graph();
+        let mut degree_histogram = Vec::new();
+        for node in graph.node_indices() {
+            let degree = graph.neighbors(node).count();
+            if degree_histogram.len() <= degree {
+                degree_histogram.resize(degree + 1, 0);
+            }
+            degree_histogram[degree] += 1;
+        }
+        Self {
+            nodes: graph.node_count(),
+            edges: graph.edge_count(),
+            degree_histogram,
+            planar: is_planar(&graph),
+            difficulty: Difficulty::of(&graph),
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let degrees = self
+            .degree_histogram
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(degree, count)| format!("{degree}: {count}"))
+            .join(", ");
+        writeln!(f, "nodes: {}", self.nodes)?;
+        writeln!(f, "edges: {}", self.edges)?;
+        writeln!(f, "crossings: {}", self.difficulty.crossings)?;
+        writeln!(f, "planar: {}", self.planar)?;
+        writeln!(f, "degrees: {degrees}")?;
+        writeln!(f, "mean degree: {:.2}", self.difficulty.mean_degree)?;
+        writeln!(f, "nodes to move: {}", self.difficulty.nodes_to_move)?;
+        write!(f, "difficulty: {:.2}", self.difficulty.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ron::ser::PrettyConfig;
+
+    use super::*;
+
+    #[test]
+    fn generated_levels_survive_a_round_trip() {
+        let cfg = GenerateConfig::default();
+        let level = LevelFile::generate(3, &cfg, 200.).unwrap();
+        let text = ron::ser::to_string_pretty(&level, PrettyConfig::default()).unwrap();
+        let read = LevelFile::parse(text.as_bytes()).unwrap();
+        assert_eq!(read.edges, level.edges);
+        assert!(read.layout_crossings() >= cfg.min_crossings as usize);
+        assert_eq!(read.solution_crossings(), Some(0));
+        assert!(Stats::of(&read).planar);
+    }
+
+    #[test]
+    fn rejects_edges_to_missing_vertices() {
+        let text = "(title: \"\", vertices: [(position: (0, 0))], edges: [(0, 1)])";
+        assert!(matches!(
+            LevelFile::parse(text.as_bytes()),
+            Err(LevelFileError::MissingVertex { end: 1, .. })
+        ));
+        let text = "(title: \"\", vertices: [(position: (0, 0))], edges: [], solution: Some([]))";
+        assert!(matches!(
+            LevelFile::parse(text.as_bytes()),
+            Err(LevelFileError::SolutionLength { .. })
+        ));
+    }
+}
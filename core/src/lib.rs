@@ -0,0 +1,13 @@
+//! The graph and geometry behind the game, with no rendering: generating
+//! puzzles, finding crossings, drawing graphs without them, the forces that lay
+//! nodes out, and level files
+#![feature(array_windows)]
+
+pub mod crossings;
+pub mod difficulty;
+pub mod drawing;
+pub mod forces;
+pub mod generate;
+pub mod geometry;
+pub mod level;
+pub mod planarity;
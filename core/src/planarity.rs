@@ -0,0 +1,807 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use petgraph::{
+    prelude::*,
+    stable_graph::{DefaultIx, IndexType},
+    visit::IntoEdgeReferences,
+};
+
+/// The neighbours of each node, in clockwise order around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationSystem<Ix: IndexType = DefaultIx>(
+    pub HashMap<NodeIndex<Ix>, Vec<NodeIndex<Ix>>>,
+);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Planarity<Ix: IndexType = DefaultIx> {
+    /// The graph can be drawn without crossings, going round each node in
+    /// this order
+    Planar(RotationSystem<Ix>),
+    /// The graph can't be drawn without crossings, because these edges form a
+    /// subdivision of K5 or K3,3
+    NonPlanar(Vec<EdgeIndex<Ix>>),
+}
+
+impl<Ix: IndexType> Planarity<Ix> {
+    pub fn is_planar(&self) -> bool {
+        matches!(self, Planarity::Planar(_))
+    }
+}
+
+/// Test whether a graph is planar with the left-right planarity test, in
+/// linear time. Self loops and repeated edges are ignored.
+///
+/// Finding a Kuratowski subgraph for a non-planar graph takes a planarity test
+/// per edge, so is quadratic.
+pub fn planarity<N, E, Ix: IndexType>(graph: &StableGraph<N, E, Undirected, Ix>) -> Planarity<Ix> {
+    let (nodes, edges) = simple_edges(graph);
+    let pairs = edges.iter().map(|(_, pair)| *pair).collect::<Vec<_>>();
+
+    if let Some(rotation) = LeftRight::new(nodes.len(), &pairs).embedding() {
+        return Planarity::Planar(RotationSystem(
+            rotation
+                .into_iter()
+                .enumerate()
+                .map(|(node, neighbours)| {
+                    (
+                        nodes[node],
+                        neighbours.into_iter().map(|other| nodes[other]).collect(),
+                    )
+                })
+                .collect(),
+        ));
+    }
+
+    // an edge-minimal non-planar subgraph is a Kuratowski subgraph
+    let mut witness = edges;
+    let mut index = 0;
+    while index < witness.len() {
+        let without = witness
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, (_, pair))| *pair)
+            .collect::<Vec<_>>();
+        if LeftRight::new(nodes.len(), &without).embedding().is_none() {
+            witness.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+    Planarity::NonPlanar(witness.into_iter().map(|(edge, _)| edge).collect())
+}
+
+/// Just whether a graph is planar, without the quadratic search for a
+/// Kuratowski subgraph when it isn't
+pub fn is_planar<N, E, Ix: IndexType>(graph: &StableGraph<N, E, Undirected, Ix>) -> bool {
+    let (nodes, edges) = simple_edges(graph);
+    let pairs = edges.iter().map(|(_, pair)| *pair).collect::<Vec<_>>();
+    rotation_system(nodes.len(), &pairs).is_some()
+}
+
+/// Each edge of a graph, with the numbers of its ends
+pub type NumberedEdges<Ix> = Vec<(EdgeIndex<Ix>, (usize, usize))>;
+
+/// Number the nodes of a graph from zero, and list its edges between those
+/// numbers without self loops or repeats
+pub fn simple_edges<N, E, Ix: IndexType>(
+    graph: &StableGraph<N, E, Undirected, Ix>,
+) -> (Vec<NodeIndex<Ix>>, NumberedEdges<Ix>) {
+    let nodes = graph.node_indices().collect::<Vec<_>>();
+    let compact = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (*node, index))
+        .collect::<HashMap<_, _>>();
+    let mut seen = HashSet::new();
+    let edges = graph
+        .edge_references()
+        .filter_map(|edge| {
+            let (a, b) = (compact[&edge.source()], compact[&edge.target()]);
+            (a != b && seen.insert((a.min(b), a.max(b)))).then_some((edge.id(), (a, b)))
+        })
+        .collect();
+    (nodes, edges)
+}
+
+/// The clockwise order of neighbours round each of `node_count` nodes in a
+/// planar drawing of a simple graph, if there is one
+pub fn rotation_system(node_count: usize, edges: &[(usize, usize)]) -> Option<Vec<Vec<usize>>> {
+    LeftRight::new(node_count, edges).embedding()
+}
+
+/// A set of return edges, from the highest to the lowest
+#[derive(Debug, Clone, Copy, Default)]
+struct Interval {
+    low: Option<usize>,
+    high: Option<usize>,
+}
+
+impl Interval {
+    fn is_empty(&self) -> bool {
+        self.low.is_none() && self.high.is_none()
+    }
+}
+
+/// Return edges that must go on opposite sides
+#[derive(Debug, Clone, Copy, Default)]
+struct ConflictPair {
+    left: Interval,
+    right: Interval,
+}
+
+impl ConflictPair {
+    fn swap(&mut self) {
+        mem::swap(&mut self.left, &mut self.right)
+    }
+}
+
+/// State of the left-right planarity test, following Brandes' "The Left-Right
+/// Planarity Test". Nodes are numbered from zero, and edges are numbered in
+/// the order the depth first search orients them.
+#[derive(Debug, Default)]
+struct LeftRight {
+    adjacency: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+    height: Vec<Option<usize>>,
+    parent_edge: Vec<Option<usize>>,
+    /// oriented edges leaving each node
+    outgoing: Vec<Vec<usize>>,
+    oriented: HashSet<(usize, usize)>,
+    source: Vec<usize>,
+    target: Vec<usize>,
+    lowpt: Vec<usize>,
+    lowpt2: Vec<usize>,
+    nesting_depth: Vec<isize>,
+    lowpt_edge: Vec<Option<usize>>,
+    reference: Vec<Option<usize>>,
+    side: Vec<isize>,
+    stack: Vec<ConflictPair>,
+    stack_bottom: Vec<usize>,
+}
+
+impl LeftRight {
+    fn new(node_count: usize, edges: &[(usize, usize)]) -> Self {
+        let mut adjacency = vec![Vec::new(); node_count];
+        for &(a, b) in edges {
+            adjacency[a].push(b);
+            adjacency[b].push(a);
+        }
+        Self {
+            adjacency,
+            height: vec![None; node_count],
+            parent_edge: vec![None; node_count],
+            outgoing: vec![Vec::new(); node_count],
+            ..Default::default()
+        }
+    }
+
+    /// The clockwise order of neighbours round each node, if the graph is
+    /// planar
+    fn embedding(mut self) -> Option<Vec<Vec<usize>>> {
+        let node_count = self.adjacency.len();
+        let edge_count = self.adjacency.iter().map(Vec::len).sum::<usize>() / 2;
+        if node_count > 2 && edge_count > 3 * node_count - 6 {
+            return None;
+        }
+
+        for node in 0..node_count {
+            if self.height[node].is_none() {
+                self.height[node] = Some(0);
+                self.roots.push(node);
+                self.orient(node);
+            }
+        }
+
+        self.sort_outgoing();
+        for root in self.roots.clone() {
+            if !self.test(root) {
+                return None;
+            }
+        }
+
+        for edge in 0..self.source.len() {
+            let sign = self.sign(edge);
+            self.nesting_depth[edge] *= sign;
+        }
+        self.sort_outgoing();
+
+        let mut rotation = Rotation::new(node_count);
+        for node in 0..node_count {
+            let mut previous = None;
+            for &edge in &self.outgoing[node] {
+                let target = self.target[edge];
+                rotation.add_clockwise(node, target, previous);
+                previous = Some(target);
+            }
+        }
+        let mut left_reference = vec![0; node_count];
+        let mut right_reference = vec![0; node_count];
+        for root in self.roots.clone() {
+            self.embed(
+                root,
+                &mut rotation,
+                &mut left_reference,
+                &mut right_reference,
+            );
+        }
+        Some(rotation.into_orders())
+    }
+
+    fn sort_outgoing(&mut self) {
+        for outgoing in &mut self.outgoing {
+            outgoing.sort_by_key(|edge| self.nesting_depth[*edge]);
+        }
+    }
+
+    /// Orient the edges away from the roots of a depth first search, finding
+    /// the lowpoints of each edge
+    fn orient(&mut self, root: usize) {
+        // an explicit stack, so long paths can't overflow the call stack
+        let mut stack = vec![Frame::new(root)];
+        while let Some(&Frame {
+            node,
+            index,
+            descended,
+        }) = stack.last()
+        {
+            if descended {
+                // back from the tree edge most recently oriented
+                let edge = *self.outgoing[node].last().unwrap();
+                self.finish_orienting(edge);
+                stack.last_mut().unwrap().next();
+                continue;
+            }
+            let Some(&other) = self.adjacency[node].get(index) else {
+                stack.pop();
+                continue;
+            };
+            if self.oriented.contains(&(node, other)) || self.oriented.contains(&(other, node)) {
+                stack.last_mut().unwrap().next();
+                continue;
+            }
+            self.oriented.insert((node, other));
+            let edge = self.source.len();
+            let height = self.height[node].unwrap();
+            self.source.push(node);
+            self.target.push(other);
+            self.outgoing[node].push(edge);
+            self.lowpt.push(height);
+            self.lowpt2.push(height);
+            self.nesting_depth.push(0);
+            self.lowpt_edge.push(None);
+            self.reference.push(None);
+            self.side.push(1);
+            self.stack_bottom.push(0);
+
+            match self.height[other] {
+                // tree edge
+                None => {
+                    self.parent_edge[other] = Some(edge);
+                    self.height[other] = Some(height + 1);
+                    stack.last_mut().unwrap().descended = true;
+                    stack.push(Frame::new(other));
+                }
+                // back edge
+                Some(other_height) => {
+                    self.lowpt[edge] = other_height;
+                    self.finish_orienting(edge);
+                    stack.last_mut().unwrap().next();
+                }
+            }
+        }
+    }
+
+    /// Work out an edge's nesting depth and pass its lowpoints up to its
+    /// parent, once everything below it is oriented
+    fn finish_orienting(&mut self, edge: usize) {
+        let source = self.source[edge];
+        let height = self.height[source].unwrap();
+        self.nesting_depth[edge] = 2 * self.lowpt[edge] as isize;
+        if self.lowpt2[edge] < height {
+            // chordal
+            self.nesting_depth[edge] += 1;
+        }
+
+        if let Some(parent) = self.parent_edge[source] {
+            if self.lowpt[edge] < self.lowpt[parent] {
+                self.lowpt2[parent] = self.lowpt[parent].min(self.lowpt2[edge]);
+                self.lowpt[parent] = self.lowpt[edge];
+            } else if self.lowpt[edge] > self.lowpt[parent] {
+                self.lowpt2[parent] = self.lowpt2[parent].min(self.lowpt[edge]);
+            } else {
+                self.lowpt2[parent] = self.lowpt2[parent].min(self.lowpt2[edge]);
+            }
+        }
+    }
+
+    /// Check the constraints on which side each back edge goes, failing if
+    /// they contradict each other
+    fn test(&mut self, root: usize) -> bool {
+        let mut stack = vec![Frame::new(root)];
+        while let Some(&Frame {
+            node,
+            index,
+            descended,
+        }) = stack.last()
+        {
+            let Some(&edge) = self.outgoing[node].get(index) else {
+                if let Some(parent) = self.parent_edge[node] {
+                    self.remove_back_edges(parent);
+                }
+                stack.pop();
+                continue;
+            };
+            if !descended {
+                let target = self.target[edge];
+                self.stack_bottom[edge] = self.stack.len();
+                if self.parent_edge[target] == Some(edge) {
+                    stack.last_mut().unwrap().descended = true;
+                    stack.push(Frame::new(target));
+                    continue;
+                }
+                self.lowpt_edge[edge] = Some(edge);
+                self.stack.push(ConflictPair {
+                    right: Interval {
+                        low: Some(edge),
+                        high: Some(edge),
+                    },
+                    ..Default::default()
+                });
+            }
+
+            // integrate new return edges
+            if self.lowpt[edge] < self.height[node].unwrap() {
+                // only a node with a parent has anywhere lower to return to
+                let parent = self.parent_edge[node].unwrap();
+                if index == 0 {
+                    self.lowpt_edge[parent] = self.lowpt_edge[edge];
+                } else if !self.add_constraints(edge, parent) {
+                    return false;
+                }
+            }
+            stack.last_mut().unwrap().next();
+        }
+        true
+    }
+
+    fn add_constraints(&mut self, edge: usize, parent: usize) -> bool {
+        let mut pair = ConflictPair::default();
+        // merge the return edges of this edge into the right interval
+        while let Some(mut other) = self.stack.pop() {
+            if !other.left.is_empty() {
+                other.swap();
+            }
+            if !other.left.is_empty() {
+                return false;
+            }
+            let Some(low) = other.right.low else {
+                return false;
+            };
+            if self.lowpt[low] > self.lowpt[parent] {
+                match pair.right.low {
+                    None => pair.right = other.right,
+                    Some(pair_low) => self.reference[pair_low] = other.right.high,
+                }
+                pair.right.low = other.right.low;
+            } else {
+                self.reference[low] = self.lowpt_edge[parent];
+            }
+            if self.stack.len() == self.stack_bottom[edge] {
+                break;
+            }
+        }
+
+        // merge the conflicting return edges of earlier siblings into the left
+        // interval
+        while self.stack.last().is_some_and(|top| {
+            self.conflicting(&top.left, edge) || self.conflicting(&top.right, edge)
+        }) {
+            let mut other = self.stack.pop().unwrap();
+            if self.conflicting(&other.right, edge) {
+                other.swap();
+            }
+            if self.conflicting(&other.right, edge) {
+                return false;
+            }
+            // merge the interval below this edge's lowpoint into the right
+            // interval
+            if let Some(pair_low) = pair.right.low {
+                self.reference[pair_low] = other.right.high;
+            }
+            if other.right.low.is_some() {
+                pair.right.low = other.right.low;
+            }
+            match pair.left.low {
+                None => pair.left = other.left,
+                Some(pair_low) => self.reference[pair_low] = other.left.high,
+            }
+            pair.left.low = other.left.low;
+        }
+
+        if !(pair.left.is_empty() && pair.right.is_empty()) {
+            self.stack.push(pair);
+        }
+        true
+    }
+
+    fn remove_back_edges(&mut self, edge: usize) {
+        let source = self.source[edge];
+        let height = self.height[source].unwrap();
+
+        // drop conflict pairs whose return edges all end at the source
+        while self
+            .stack
+            .last()
+            .is_some_and(|top| self.lowest(top) == Some(height))
+        {
+            let pair = self.stack.pop().unwrap();
+            if let Some(low) = pair.left.low {
+                self.side[low] = -1;
+            }
+        }
+
+        // trim the one conflict pair that might be left
+        if let Some(mut pair) = self.stack.pop() {
+            while let Some(high) = pair.left.high.filter(|high| self.target[*high] == source) {
+                pair.left.high = self.reference[high];
+            }
+            if pair.left.high.is_none() {
+                if let Some(low) = pair.left.low.take() {
+                    self.reference[low] = pair.right.low;
+                    self.side[low] = -1;
+                }
+            }
+            while let Some(high) = pair.right.high.filter(|high| self.target[*high] == source) {
+                pair.right.high = self.reference[high];
+            }
+            if pair.right.high.is_none() {
+                if let Some(low) = pair.right.low.take() {
+                    self.reference[low] = pair.left.low;
+                    self.side[low] = -1;
+                }
+            }
+            self.stack.push(pair);
+        }
+
+        // the edge goes on the side of its highest return edge
+        if self.lowpt[edge] < height {
+            if let Some(top) = self.stack.last() {
+                let (left, right) = (top.left.high, top.right.high);
+                self.reference[edge] = match (left, right) {
+                    (Some(left), Some(right)) if self.lowpt[left] > self.lowpt[right] => Some(left),
+                    (Some(left), None) => Some(left),
+                    _ => right,
+                };
+            }
+        }
+    }
+
+    fn conflicting(&self, interval: &Interval, edge: usize) -> bool {
+        interval
+            .high
+            .is_some_and(|high| self.lowpt[high] > self.lowpt[edge])
+    }
+
+    fn lowest(&self, pair: &ConflictPair) -> Option<usize> {
+        [pair.left.low, pair.right.low]
+            .into_iter()
+            .flatten()
+            .map(|low| self.lowpt[low])
+            .min()
+    }
+
+    /// Resolve which side an edge goes on, relative to its parent
+    fn sign(&mut self, edge: usize) -> isize {
+        let mut chain = vec![edge];
+        while let Some(next) = self.reference[*chain.last().unwrap()] {
+            chain.push(next);
+        }
+        for pair in (0..chain.len() - 1).rev() {
+            let (edge, next) = (chain[pair], chain[pair + 1]);
+            self.side[edge] *= self.side[next];
+            self.reference[edge] = None;
+        }
+        self.side[edge]
+    }
+
+    fn embed(
+        &self,
+        root: usize,
+        rotation: &mut Rotation,
+        left_reference: &mut [usize],
+        right_reference: &mut [usize],
+    ) {
+        // each node and how far through its edges the search is
+        let mut stack = vec![(root, 0)];
+        while let Some((node, index)) = stack.pop() {
+            let Some(&edge) = self.outgoing[node].get(index) else {
+                continue;
+            };
+            stack.push((node, index + 1));
+            let target = self.target[edge];
+            if self.parent_edge[target] == Some(edge) {
+                rotation.add_first(target, node);
+                left_reference[node] = target;
+                right_reference[node] = target;
+                stack.push((target, 0));
+            } else if self.side[edge] == 1 {
+                rotation.add_clockwise(target, node, Some(right_reference[target]));
+            } else {
+                rotation.add_anticlockwise(target, node, Some(left_reference[target]));
+                left_reference[target] = node;
+            }
+        }
+    }
+}
+
+/// A node the depth first search is part way through
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    node: usize,
+    /// How far through the node's edges the search is
+    index: usize,
+    /// Whether the search has gone down the edge at `index` and come back
+    descended: bool,
+}
+
+impl Frame {
+    fn new(node: usize) -> Self {
+        Self {
+            node,
+            index: 0,
+            descended: false,
+        }
+    }
+
+    fn next(&mut self) {
+        self.index += 1;
+        self.descended = false;
+    }
+}
+
+/// Cyclic orders of neighbours round each node, as linked lists
+#[derive(Debug)]
+struct Rotation {
+    first: Vec<Option<usize>>,
+    clockwise: HashMap<(usize, usize), usize>,
+    anticlockwise: HashMap<(usize, usize), usize>,
+}
+
+impl Rotation {
+    fn new(node_count: usize) -> Self {
+        Self {
+            first: vec![None; node_count],
+            clockwise: HashMap::new(),
+            anticlockwise: HashMap::new(),
+        }
+    }
+
+    /// Put `end` directly clockwise of `reference` round `start`
+    fn add_clockwise(&mut self, start: usize, end: usize, reference: Option<usize>) {
+        let Some(reference) = reference else {
+            self.clockwise.insert((start, end), end);
+            self.anticlockwise.insert((start, end), end);
+            self.first[start] = Some(end);
+            return;
+        };
+        let after = self.clockwise[&(start, reference)];
+        self.clockwise.insert((start, reference), end);
+        self.clockwise.insert((start, end), after);
+        self.anticlockwise.insert((start, after), end);
+        self.anticlockwise.insert((start, end), reference);
+    }
+
+    /// Put `end` directly anticlockwise of `reference` round `start`
+    fn add_anticlockwise(&mut self, start: usize, end: usize, reference: Option<usize>) {
+        let Some(reference) = reference else {
+            return self.add_clockwise(start, end, None);
+        };
+        let before = self.anticlockwise[&(start, reference)];
+        self.add_clockwise(start, end, Some(before));
+        if self.first[start] == Some(reference) {
+            self.first[start] = Some(end);
+        }
+    }
+
+    fn add_first(&mut self, start: usize, end: usize) {
+        self.add_anticlockwise(start, end, self.first[start]);
+    }
+
+    fn into_orders(self) -> Vec<Vec<usize>> {
+        self.first
+            .iter()
+            .enumerate()
+            .map(|(node, first)| {
+                let Some(first) = *first else {
+                    return Vec::new();
+                };
+                let mut order = vec![first];
+                let mut next = self.clockwise[&(node, first)];
+                while next != first {
+                    order.push(next);
+                    next = self.clockwise[&(node, next)];
+                }
+                order
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::{unionfind::UnionFind, visit::NodeIndexable};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::generate::{GenerateConfig, Generator, PuzzleGenerator};
+
+    fn from_edges(count: usize, edges: &[(usize, usize)]) -> StableUnGraph<(), ()> {
+        let mut graph = StableUnGraph::default();
+        let nodes = (0..count).map(|_| graph.add_node(())).collect::<Vec<_>>();
+        for &(a, b) in edges {
+            graph.add_edge(nodes[a], nodes[b], ());
+        }
+        graph
+    }
+
+    fn complete(count: usize) -> Vec<(usize, usize)> {
+        (0..count)
+            .flat_map(|a| (a + 1..count).map(move |b| (a, b)))
+            .collect()
+    }
+
+    fn utility() -> Vec<(usize, usize)> {
+        (0..3).flat_map(|a| (3..6).map(move |b| (a, b))).collect()
+    }
+
+    /// Put a new node in the middle of every edge
+    fn subdivide(count: usize, edges: &[(usize, usize)]) -> (usize, Vec<(usize, usize)>) {
+        let split = edges
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &(a, b))| [(a, count + index), (count + index, b)])
+            .collect();
+        (count + edges.len(), split)
+    }
+
+    fn assert_kuratowski(count: usize, edges: &[(usize, usize)]) {
+        let graph = from_edges(count, edges);
+        assert!(!is_planar(&graph));
+        let Planarity::NonPlanar(witness) = planarity(&graph) else {
+            panic!("{edges:?} is planar");
+        };
+        // the graph is already a Kuratowski subgraph, so all of it is needed
+        assert_eq!(witness.len(), edges.len());
+    }
+
+    #[test]
+    fn kuratowski_graphs_are_not_planar() {
+        assert_kuratowski(5, &complete(5));
+        assert_kuratowski(6, &utility());
+        let (count, edges) = subdivide(5, &complete(5));
+        assert_kuratowski(count, &edges);
+        let (count, edges) = subdivide(6, &utility());
+        assert_kuratowski(count, &edges);
+    }
+
+    #[test]
+    fn witness_is_a_non_planar_part_of_a_bigger_graph() {
+        // K3,3 hanging off a wheel
+        let mut edges = (1..8)
+            .flat_map(|node| [(0, node), (node, node % 7 + 1)])
+            .collect::<Vec<_>>();
+        edges.extend(utility().iter().map(|(a, b)| (a + 7, b + 7)));
+        let graph = from_edges(13, &edges);
+        let Planarity::NonPlanar(witness) = planarity(&graph) else {
+            panic!("the graph is planar");
+        };
+        assert_eq!(witness.len(), 9);
+        let mut rest = graph.clone();
+        rest.retain_edges(|_, edge| witness.contains(&edge));
+        assert!(!is_planar(&rest));
+    }
+
+    /// Check Euler's formula on the faces traced out by a rotation system,
+    /// for each piece of the graph with an edge
+    fn assert_euler(graph: &StableUnGraph<(), ()>, rotation: &RotationSystem) {
+        let mut darts = HashSet::new();
+        let mut faces = 0;
+        for (&node, neighbours) in &rotation.0 {
+            for &start in neighbours {
+                if !darts.insert((node, start)) {
+                    continue;
+                }
+                faces += 1;
+                let (mut from, mut to) = (node, start);
+                loop {
+                    let around = &rotation.0[&to];
+                    let back = around.iter().position(|other| *other == from).unwrap();
+                    let next = around[(back + 1) % around.len()];
+                    (from, to) = (to, next);
+                    if !darts.insert((from, to)) {
+                        break;
+                    }
+                }
+            }
+        }
+        assert_eq!(darts.len(), 2 * graph.edge_count());
+
+        let mut pieces = UnionFind::new(graph.node_bound());
+        for edge in graph.edge_references() {
+            pieces.union(edge.source().index(), edge.target().index());
+        }
+        let with_edges = graph
+            .edge_references()
+            .map(|edge| pieces.find(edge.source().index()))
+            .collect::<HashSet<_>>()
+            .len();
+        let nodes = graph
+            .node_indices()
+            .filter(|node| graph.neighbors(*node).next().is_some())
+            .count();
+        assert_eq!(
+            nodes as isize - graph.edge_count() as isize + faces,
+            2 * with_edges as isize
+        );
+    }
+
+    #[test]
+    fn random_planar_graphs_have_consistent_embeddings() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for generator in Generator::ALL {
+            for num_circles in [3, 6, 10] {
+                let cfg = GenerateConfig {
+                    generator,
+                    min_crossings: 0,
+                    min_difficulty: 0.,
+                    max_difficulty: f32::INFINITY,
+                    num_circles,
+                    limit_nodes: usize::MAX,
+                    node_starting_distance: 100.,
+                    node_starting_random_offset: 20.,
+                };
+                for _ in 0..8 {
+                    let mut graph = generator.generate(&mut rng, &cfg).unwrap();
+                    let Planarity::Planar(rotation) = planarity(&graph) else {
+                        panic!("{generator:?} made a non-planar graph");
+                    };
+                    assert_euler(&graph, &rotation);
+
+                    // an extra edge might make it non-planar, but the answer
+                    // has to be the same either way
+                    let nodes = graph.node_indices().collect::<Vec<_>>();
+                    let a = nodes[rng.gen_range(0..nodes.len())];
+                    let b = nodes[rng.gen_range(0..nodes.len())];
+                    if a != b && graph.find_edge(a, b).is_none() {
+                        graph.add_edge(a, b, ());
+                    }
+                    match planarity(&graph) {
+                        Planarity::Planar(rotation) => {
+                            assert!(is_planar(&graph));
+                            assert_euler(&graph, &rotation);
+                        }
+                        Planarity::NonPlanar(_) => assert!(!is_planar(&graph)),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn long_paths_dont_overflow_the_stack() {
+        let count = 200_000;
+        let edges = (0..count)
+            .map(|node| (node, (node + 1) % count))
+            .collect::<Vec<_>>();
+        let graph = from_edges(count, &edges);
+        let Planarity::Planar(rotation) = planarity(&graph) else {
+            panic!("a cycle is planar");
+        };
+        assert_euler(&graph, &rotation);
+    }
+}
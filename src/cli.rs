@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use clap::Parser;
+
+use crate::{config::Cfg, generate::Generator, input::Action};
+
+/// Move points around until none of the lines between them cross
+#[derive(Parser, Debug)]
+#[command(version)]
+pub(crate) struct Cli {
+    /// A graph to play, as DOT, GraphML, GML or an edge list
+    pub graph: Option<PathBuf>,
+    /// A level from `assets/levels` to play, by name
+    #[arg(long)]
+    pub level: Option<String>,
+    /// Generate every puzzle from this seed
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// How to generate puzzles: circles, delaunay, maximal, grid, triangular,
+    /// outerplanar or tree
+    #[arg(long)]
+    pub generator: Option<Generator>,
+    /// Change a setting, with the value written in RON, like `num_circles=5`
+    #[arg(long = "set", value_name = "NAME=VALUE")]
+    pub settings: Vec<String>,
+    /// Go straight to free play without the story
+    #[arg(long)]
+    pub no_story: bool,
+    /// The Ink story to play, relative to the assets directory
+    #[arg(long, default_value = "main.ink")]
+    pub story: String,
+    /// Hide the world and resource inspectors
+    #[arg(long)]
+    pub no_inspectors: bool,
+}
+
+impl Cli {
+    /// Apply the settings given on the command line over the saved ones
+    pub(crate) fn apply(&self, cfg: &mut Cfg) -> Result<(), String> {
+        for setting in &self.settings {
+            cfg.set(setting)?;
+        }
+        if let Some(seed) = self.seed {
+            cfg.seed = Some(seed);
+        }
+        if let Some(generator) = self.generator {
+            cfg.generator = generator;
+        }
+        *cfg = cfg.clone().validated();
+        Ok(())
+    }
+
+    /// Actions to take once the game has started
+    pub(crate) fn start_actions(&self) -> StartActions {
+        StartActions(self.level.iter().cloned().map(Action::Level).collect())
+    }
+}
+
+/// Actions from the command line, sent once everything has loaded
+#[derive(Resource, Default, Debug)]
+pub(crate) struct StartActions(pub Vec<Action>);
+
+pub(crate) fn send_start_actions(
+    mut start: ResMut<StartActions>,
+    mut actions: EventWriter<Action>,
+) {
+    for action in start.0.drain(..) {
+        actions.send(action);
+    }
+}
@@ -1,26 +1,61 @@
-use bevy::prelude::*;
+use std::{fmt::Debug, fs, io, path::PathBuf, time::Duration};
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    reflect::{serde::TypedReflectDeserializer, Struct, TypeRegistry},
+};
 use bevy_inspector_egui::prelude::*;
+use directories::ProjectDirs;
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+use thiserror::Error;
+
+use planarity_core::generate::{GenerateConfig, Generator};
+
+// the smallest and largest value of each number setting, for both the
+// inspector and `Cfg::validated`
+const MIN_CROSSINGS_BOUNDS: (u32, u32) = (0, 100);
+const DIFFICULTY_BOUNDS: (f32, f32) = (0., f32::INFINITY);
+const NUM_CIRCLES_BOUNDS: (usize, usize) = (1, 9);
+const LIMIT_NODES_BOUNDS: (usize, usize) = (2, 1000);
+const STARTING_DISTANCE_BOUNDS: (f32, f32) = (1., 10000.);
+const LENGTH_BOUNDS: (f32, f32) = (0., 10000.);
+const FIELD_BASE_BOUNDS: (f64, f64) = (0., 10.);
 
-#[derive(Resource, Reflect, Clone, Debug, InspectorOptions)]
+#[derive(Resource, Reflect, Clone, Debug, InspectorOptions, Serialize, Deserialize)]
 #[reflect(Resource, InspectorOptions, Default)]
-pub(crate) struct Cfg {
+#[serde(default)]
+pub struct Cfg {
     pub debug_vecs: bool,
     pub debug_print: bool,
-    #[inspector(min = 1, max = 9)]
+    pub show_solution: bool,
+    /// Generate every puzzle from this seed, or from a random one if unset
+    pub seed: Option<u64>,
+    pub generator: Generator,
+    /// Every puzzle starts with at least this many crossings
+    #[inspector(min = MIN_CROSSINGS_BOUNDS.0, max = MIN_CROSSINGS_BOUNDS.1)]
+    pub min_crossings: u32,
+    /// Puzzles are regenerated until their difficulty score is in this range
+    #[inspector(min = DIFFICULTY_BOUNDS.0)]
+    pub min_difficulty: f32,
+    #[inspector(min = DIFFICULTY_BOUNDS.0)]
+    pub max_difficulty: f32,
+    #[inspector(min = NUM_CIRCLES_BOUNDS.0, max = NUM_CIRCLES_BOUNDS.1)]
     pub num_circles: usize,
-    #[inspector(min = 2, max = 1000)]
+    #[inspector(min = LIMIT_NODES_BOUNDS.0, max = LIMIT_NODES_BOUNDS.1)]
     pub limit_nodes: usize,
-    #[inspector(min = 1.0, max = 10000.)]
+    #[inspector(min = STARTING_DISTANCE_BOUNDS.0, max = STARTING_DISTANCE_BOUNDS.1)]
     pub node_starting_distance: f32,
-    #[inspector(min = 0.0, max = 10000.)]
+    #[inspector(min = LENGTH_BOUNDS.0, max = LENGTH_BOUNDS.1)]
     pub node_starting_random_offset: f32,
-    #[inspector(min = 0.0, max = 10000.)]
+    #[inspector(min = LENGTH_BOUNDS.0, max = LENGTH_BOUNDS.1)]
     pub move_speed: f32,
-    #[inspector(min = 0.0, max = 10000.)]
+    #[inspector(min = LENGTH_BOUNDS.0, max = LENGTH_BOUNDS.1)]
     pub target_centre_length: f32,
-    #[inspector(min = 0.0, max = 10000.)]
+    #[inspector(min = LENGTH_BOUNDS.0, max = LENGTH_BOUNDS.1)]
     pub target_point_length: f32,
-    #[inspector(speed = 0.001, min = 0.0, max = 10.0)]
+    #[inspector(speed = 0.001, min = FIELD_BASE_BOUNDS.0, max = FIELD_BASE_BOUNDS.1)]
     pub field_base: f64,
 }
 
@@ -29,6 +64,12 @@ impl Default for Cfg {
         Self {
             debug_vecs: false,
             debug_print: false,
+            show_solution: false,
+            seed: None,
+            generator: Generator::Circles,
+            min_crossings: 1,
+            min_difficulty: 0.,
+            max_difficulty: f32::INFINITY,
             num_circles: 10,
             limit_nodes: 6,
             node_starting_distance: 100.,
@@ -41,6 +82,265 @@ impl Default for Cfg {
     }
 }
 
+/// How long settings have to stay the same before they're written out, so
+/// dragging a value in the inspector doesn't write every frame
+const SETTINGS_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+impl Cfg {
+    /// The saved settings, or the defaults if there aren't any
+    pub(crate) fn load() -> Self {
+        let Some(path) = settings_path() else {
+            return Self::default();
+        };
+        let cfg = match fs::read_to_string(&path) {
+            Ok(text) => match ron::from_str::<Self>(&text) {
+                Ok(cfg) => cfg,
+                Err(error) => {
+                    eprintln!("Couldn't read settings {}: {error}", path.display());
+                    return Self::default();
+                }
+            },
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    eprintln!("Couldn't read settings {}: {error}", path.display());
+                }
+                return Self::default();
+            }
+        };
+        cfg.validated()
+    }
+
+    /// Replace anything outside the inspector's ranges with its default
+    pub fn validated(self) -> Self {
+        let default = Self::default();
+        let cfg = Self {
+            min_crossings: checked(
+                "min_crossings",
+                self.min_crossings,
+                MIN_CROSSINGS_BOUNDS,
+                default.min_crossings,
+            ),
+            min_difficulty: checked(
+                "min_difficulty",
+                self.min_difficulty,
+                DIFFICULTY_BOUNDS,
+                default.min_difficulty,
+            ),
+            max_difficulty: checked(
+                "max_difficulty",
+                self.max_difficulty,
+                DIFFICULTY_BOUNDS,
+                default.max_difficulty,
+            ),
+            num_circles: checked(
+                "num_circles",
+                self.num_circles,
+                NUM_CIRCLES_BOUNDS,
+                default.num_circles,
+            ),
+            limit_nodes: checked(
+                "limit_nodes",
+                self.limit_nodes,
+                LIMIT_NODES_BOUNDS,
+                default.limit_nodes,
+            ),
+            node_starting_distance: checked(
+                "node_starting_distance",
+                self.node_starting_distance,
+                STARTING_DISTANCE_BOUNDS,
+                default.node_starting_distance,
+            ),
+            node_starting_random_offset: checked(
+                "node_starting_random_offset",
+                self.node_starting_random_offset,
+                LENGTH_BOUNDS,
+                default.node_starting_random_offset,
+            ),
+            move_speed: checked(
+                "move_speed",
+                self.move_speed,
+                LENGTH_BOUNDS,
+                default.move_speed,
+            ),
+            target_centre_length: checked(
+                "target_centre_length",
+                self.target_centre_length,
+                LENGTH_BOUNDS,
+                default.target_centre_length,
+            ),
+            target_point_length: checked(
+                "target_point_length",
+                self.target_point_length,
+                LENGTH_BOUNDS,
+                default.target_point_length,
+            ),
+            field_base: checked(
+                "field_base",
+                self.field_base,
+                FIELD_BASE_BOUNDS,
+                default.field_base,
+            ),
+            ..self
+        };
+        if cfg.min_difficulty > cfg.max_difficulty {
+            eprintln!(
+                "Warning: setting min_difficulty is {:?}, above max_difficulty {:?}, so using {:?} to {:?}",
+                cfg.min_difficulty,
+                cfg.max_difficulty,
+                default.min_difficulty,
+                default.max_difficulty
+            );
+            return Self {
+                min_difficulty: default.min_difficulty,
+                max_difficulty: default.max_difficulty,
+                ..cfg
+            };
+        }
+        cfg
+    }
+
+    /// The settings that puzzle generation uses
+    pub fn generate_config(&self) -> GenerateConfig {
+        GenerateConfig {
+            generator: self.generator,
+            min_crossings: self.min_crossings,
+            min_difficulty: self.min_difficulty,
+            max_difficulty: self.max_difficulty,
+            num_circles: self.num_circles,
+            limit_nodes: self.limit_nodes,
+            node_starting_distance: self.node_starting_distance,
+            node_starting_random_offset: self.node_starting_random_offset,
+        }
+    }
+
+    /// Set one field from `NAME=VALUE`, with the value written the same way as
+    /// in the settings file
+    pub fn set(&mut self, setting: &str) -> Result<(), String> {
+        let (name, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("{setting} should be NAME=VALUE"))?;
+        let name = name.trim();
+        let field = self
+            .field_mut(name)
+            .ok_or_else(|| format!("unknown setting {name}"))?;
+        let registry = setting_types();
+        let registration = field
+            .get_represented_type_info()
+            .and_then(|info| registry.get(info.type_id()))
+            .ok_or_else(|| format!("{name} can't be set by name"))?;
+        let read = || {
+            let mut deserializer = ron::Deserializer::from_str(value)?;
+            let value = TypedReflectDeserializer::new(registration, &registry)
+                .deserialize(&mut deserializer)?;
+            deserializer.end()?;
+            Ok::<_, ron::Error>(value)
+        };
+        let value = read().map_err(|error| format!("{name}: {error}"))?;
+        field.apply(&*value);
+        Ok(())
+    }
+
+    /// Copy across every setting that differs between `before` and `after`
+    pub(crate) fn copy_changes(&mut self, before: &Self, after: &Self) {
+        for index in 0..after.field_len() {
+            let (Some(before), Some(after)) = (before.field_at(index), after.field_at(index))
+            else {
+                continue;
+            };
+            if before.reflect_partial_eq(after) != Some(true) {
+                if let Some(field) = self.field_at_mut(index) {
+                    field.apply(after);
+                }
+            }
+        }
+    }
+
+    fn save(&self) -> Result<(), SettingsError> {
+        let path = settings_path().ok_or(SettingsError::NoDirectory)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum SettingsError {
+    #[error("Could not write settings: {0}")]
+    Io(#[from] io::Error),
+    #[error("Could not write settings: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("No directory to save settings in")]
+    NoDirectory,
+}
+
+/// The types of every setting, for reading one by name
+fn setting_types() -> TypeRegistry {
+    let mut registry = TypeRegistry::default();
+    registry.register::<Option<u64>>();
+    registry.register::<Generator>();
+    registry
+}
+
+fn settings_path() -> Option<PathBuf> {
+    Some(
+        ProjectDirs::from("", "", "planarity")?
+            .config_dir()
+            .join("settings.ron"),
+    )
+}
+
+fn checked<T: PartialOrd + Debug>(name: &str, value: T, (min, max): (T, T), default: T) -> T {
+    let range = min..=max;
+    if range.contains(&value) {
+        value
+    } else {
+        eprintln!("Warning: setting {name} is {value:?}, outside {range:?}, so using {default:?}");
+        default
+    }
+}
+
+/// The settings as they are in the settings file. Only changes made in the
+/// inspector are copied here to be saved, since everything else that changes
+/// [`Cfg`] (the command line, story tags and resuming a game) bypasses change
+/// detection and only lasts for this run.
+#[derive(Resource, Clone, Debug)]
+pub(crate) struct SavedSettings(pub Cfg);
+
+/// Write the settings out once they've stopped changing, or when the game
+/// closes
+pub(crate) fn save_settings(
+    cfg: Res<Cfg>,
+    mut saved: ResMut<SavedSettings>,
+    time: Res<Time>,
+    mut exits: EventReader<AppExit>,
+    mut pending: Local<Option<Timer>>,
+    mut last_frame: Local<Option<Cfg>>,
+) {
+    if let Some(before) = &*last_frame {
+        if cfg.is_changed() && !cfg.is_added() {
+            saved.0.copy_changes(before, &cfg);
+            *pending = Some(Timer::new(SETTINGS_SAVE_DELAY, TimerMode::Once));
+        }
+    }
+    *last_frame = Some(cfg.clone());
+    let Some(timer) = &mut *pending else {
+        return;
+    };
+    let exiting = exits.read().next().is_some();
+    if !timer.tick(time.delta()).finished() && !exiting {
+        return;
+    }
+    *pending = None;
+    if let Err(error) = saved.0.save() {
+        eprintln!("Couldn't save settings: {error}");
+    }
+}
+
 // pub(crate) fn settings(mut contexts: EguiContexts, mut cfg: ResMut<Cfg>) {
 //     egui::Window::new("Settings").show(contexts.ctx_mut(), |ui| {
 //         ui.checkbox(&mut cfg.debug_vecs, "Debug Vectors");
@@ -0,0 +1,256 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use itertools::Itertools;
+use planarity_core::crossings::sweep_crossings;
+
+use crate::{Edge, Node};
+
+/// Side length of the grid cells used to find the edges near a moved edge
+const CELL_SIZE: f32 = 64.;
+
+/// Which edges cross which, kept up to date as nodes move
+#[derive(Resource, Default, Debug)]
+pub(crate) struct Crossings {
+    /// for each edge, the edges it crosses
+    crossed: HashMap<Entity, HashSet<Entity>>,
+    edges: HashMap<Entity, Edge>,
+    /// edges whose endpoints are both nodes, with their current positions
+    lines: HashMap<Entity, [Vec2; 2]>,
+    incident: HashMap<Entity, HashSet<Entity>>,
+    cells: HashMap<IVec2, HashSet<Entity>>,
+    /// edges whose crossings might have changed since they were last taken
+    changed: HashSet<Entity>,
+    /// the nodes at the ends of those edges
+    changed_nodes: HashSet<Entity>,
+}
+
+impl Crossings {
+    /// Number of crossing pairs of edges
+    pub(crate) fn count(&self) -> usize {
+        self.crossed.values().map(HashSet::len).sum::<usize>() / 2
+    }
+
+    pub(crate) fn crosses_any(&self, edge: Entity) -> bool {
+        self.crossed
+            .get(&edge)
+            .is_some_and(|crossed| !crossed.is_empty())
+    }
+
+    /// Whether any edge at a node crosses another edge
+    pub(crate) fn node_crosses(&self, node: Entity) -> bool {
+        self.incident
+            .get(&node)
+            .is_some_and(|edges| edges.iter().any(|edge| self.crosses_any(*edge)))
+    }
+
+    /// The edges, and the nodes at their ends, whose crossings might have
+    /// changed since this was last called
+    pub(crate) fn take_changed(&mut self) -> (HashSet<Entity>, HashSet<Entity>) {
+        (
+            mem::take(&mut self.changed),
+            mem::take(&mut self.changed_nodes),
+        )
+    }
+
+    fn mark_changed(&mut self, edge: Entity) {
+        self.changed.insert(edge);
+        if let Some(Edge(start, end)) = self.edges.get(&edge) {
+            self.changed_nodes.extend([*start, *end]);
+        }
+    }
+
+    fn remove(&mut self, edge: Entity) {
+        self.mark_changed(edge);
+        let Some(Edge(start, end)) = self.edges.remove(&edge) else {
+            return;
+        };
+        for node in [start, end] {
+            if let Some(incident) = self.incident.get_mut(&node) {
+                incident.remove(&edge);
+            }
+        }
+        if let Some(line) = self.lines.remove(&edge) {
+            for cell in cells(line) {
+                if let Some(entities) = self.cells.get_mut(&cell) {
+                    entities.remove(&edge);
+                }
+            }
+        }
+        for other in self.crossed.remove(&edge).into_iter().flatten() {
+            if let Some(crossed) = self.crossed.get_mut(&other) {
+                crossed.remove(&edge);
+            }
+            self.mark_changed(other);
+        }
+    }
+
+    fn insert(&mut self, entity: Entity, edge: Edge, line: Option<[Vec2; 2]>) {
+        self.edges.insert(entity, edge);
+        for node in [edge.0, edge.1] {
+            self.incident.entry(node).or_default().insert(entity);
+        }
+        self.mark_changed(entity);
+        let Some(line) = line else {
+            return;
+        };
+
+        let crossed: HashSet<Entity> = cells(line)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .unique()
+            .filter(|other| edges_cross((edge, line), (self.edges[other], self.lines[other])))
+            .collect();
+        for other in &crossed {
+            self.crossed.entry(*other).or_default().insert(entity);
+            self.mark_changed(*other);
+        }
+        self.crossed.insert(entity, crossed);
+
+        for cell in cells(line) {
+            self.cells.entry(cell).or_default().insert(entity);
+        }
+        self.lines.insert(entity, line);
+    }
+
+    fn rebuild(&mut self, edges: impl IntoIterator<Item = (Entity, Edge, Option<[Vec2; 2]>)>) {
+        // every edge is restyled, along with any left over from before
+        *self = Self {
+            changed: mem::take(&mut self.changed),
+            changed_nodes: mem::take(&mut self.changed_nodes),
+            ..default()
+        };
+        let mut located = Vec::new();
+        for (entity, edge, line) in edges {
+            self.edges.insert(entity, edge);
+            for node in [edge.0, edge.1] {
+                self.incident.entry(node).or_default().insert(entity);
+            }
+            self.mark_changed(entity);
+            if let Some(line) = line {
+                located.push((entity, edge, line));
+                self.lines.insert(entity, line);
+                self.crossed.insert(entity, HashSet::new());
+                for cell in cells(line) {
+                    self.cells.entry(cell).or_default().insert(entity);
+                }
+            }
+        }
+
+        let segments = located.iter().map(|(_, _, line)| *line).collect_vec();
+        let crossings = sweep_crossings(&segments, |x, y| {
+            let (_, x_edge, x_line) = located[x];
+            let (_, y_edge, y_line) = located[y];
+            edges_cross((x_edge, x_line), (y_edge, y_line))
+        });
+        for (x, y) in crossings {
+            let (x, y) = (located[x].0, located[y].0);
+            self.crossed.entry(x).or_default().insert(y);
+            self.crossed.entry(y).or_default().insert(x);
+        }
+    }
+}
+
+/// Keep [`Crossings`] up to date with the edges incident to nodes that moved,
+/// only checking everything again when most of the graph has changed
+pub(crate) fn update_crossings(
+    mut crossings: ResMut<Crossings>,
+    edges: Query<(Entity, &Edge)>,
+    added_edges: Query<Entity, Added<Edge>>,
+    mut removed_edges: RemovedComponents<Edge>,
+    moved_nodes: Query<Entity, (With<Node>, Changed<Transform>)>,
+    mut removed_nodes: RemovedComponents<Node>,
+    nodes: Query<&Transform, With<Node>>,
+) {
+    let line = |Edge(start, end): Edge| {
+        let [Ok(start), Ok(end)] = [nodes.get(start), nodes.get(end)] else {
+            return None;
+        };
+        Some([start.translation.xy(), end.translation.xy()])
+    };
+
+    let removed = removed_edges.read().collect_vec();
+    let mut dirty: HashSet<Entity> = added_edges.iter().collect();
+    for node in moved_nodes.iter().chain(removed_nodes.read()) {
+        if let Some(incident) = crossings.incident.get(&node) {
+            dirty.extend(incident);
+        }
+    }
+    if removed.is_empty() && dirty.is_empty() {
+        return;
+    }
+
+    if dirty.len() * 4 > edges.iter().len() {
+        crossings.rebuild(
+            edges
+                .iter()
+                .map(|(entity, edge)| (entity, *edge, line(*edge))),
+        );
+        return;
+    }
+    for entity in removed {
+        crossings.remove(entity);
+    }
+    for entity in dirty {
+        crossings.remove(entity);
+        if let Ok((entity, edge)) = edges.get(entity) {
+            crossings.insert(entity, *edge, line(*edge));
+        }
+    }
+}
+
+/// Whether two edges count as crossing each other
+pub(crate) fn edges_cross(
+    (x_edge, x_line): (Edge, [Vec2; 2]),
+    (y_edge, y_line): (Edge, [Vec2; 2]),
+) -> bool {
+    planarity_core::crossings::edges_cross(
+        ([x_edge.0, x_edge.1], x_line),
+        ([y_edge.0, y_edge.1], y_line),
+    )
+}
+
+/// How far past a grid line, in cells, a line still counts as being in the
+/// cell beyond, so two edges that cross on a grid line always share a cell
+const CELL_TOLERANCE: f32 = 1e-3;
+
+/// Grid cells a line passes through, walked a column at a time so a long edge
+/// only takes up the cells along it
+fn cells([start, end]: [Vec2; 2]) -> impl Iterator<Item = IVec2> {
+    // walk along whichever axis the line is longer in, so each column only
+    // has a cell or two of it
+    let steep = (end.y - start.y).abs() > (end.x - start.x).abs();
+    let across = move |v: Vec2| if steep { Vec2::new(v.y, v.x) } else { v };
+    let (start, end) = (across(start) / CELL_SIZE, across(end) / CELL_SIZE);
+    let (start, end) = if start.x <= end.x {
+        (start, end)
+    } else {
+        (end, start)
+    };
+    let slope = if end.x > start.x {
+        (end.y - start.y) / (end.x - start.x)
+    } else {
+        0.
+    };
+    let first = (start.x - CELL_TOLERANCE).floor() as i32;
+    let last = (end.x + CELL_TOLERANCE).floor() as i32;
+    (first..=last).flat_map(move |column| {
+        // the part of the line inside this column, including its edges
+        let left = (column as f32).max(start.x);
+        let right = (column as f32 + 1.).min(end.x);
+        let [y0, y1] = [left, right].map(|x| start.y + (x - start.x) * slope);
+        let low = (y0.min(y1) - CELL_TOLERANCE).floor() as i32;
+        let high = (y0.max(y1) + CELL_TOLERANCE).floor() as i32;
+        (low..=high).map(move |row| {
+            if steep {
+                IVec2::new(row, column)
+            } else {
+                IVec2::new(column, row)
+            }
+        })
+    })
+}
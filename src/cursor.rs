@@ -1,5 +1,7 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
+use crate::level::Pinned;
+
 /// We will store the world position of the mouse cursor here.
 #[derive(Resource, Default)]
 pub(super) struct CursorWorldCoords(pub Vec2);
@@ -44,7 +46,7 @@ pub(super) fn update_cursor(
 
 pub(crate) fn track_cursor(
     pointer: Res<CursorWorldCoords>,
-    mut entities: Query<(&mut Transform, &TrackCursor)>,
+    mut entities: Query<(&mut Transform, &TrackCursor), Without<Pinned>>,
 ) {
     if pointer.is_changed() {
         for (mut transform, TrackCursor(track)) in &mut entities {
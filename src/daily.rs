@@ -0,0 +1,192 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use chrono::{Datelike, Local, NaiveDate};
+use directories::ProjectDirs;
+
+use planarity_core::generate::GenerateConfig;
+
+use crate::{
+    generate::Generator, hint::HintsUsed, history::History, input::Action, IntersectionsCount,
+};
+
+/// The daily puzzle being played, if there is one
+#[derive(Resource, Default, Debug)]
+pub(crate) struct Daily(pub Option<DailyRun>);
+
+#[derive(Debug, Clone)]
+pub(crate) struct DailyRun {
+    date: NaiveDate,
+    started: f64,
+    moves_before: u32,
+    hints_before: u32,
+    /// Whether there have been any crossings yet, so the level isn't counted
+    /// as solved before it's been generated
+    tangled: bool,
+    result: Option<DailyResult>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DailyResult {
+    pub seconds: f64,
+    pub moves: u32,
+    pub hints: u32,
+}
+
+/// The seed, number of circles and node limit of the puzzle for a date, which
+/// is the same for everyone
+fn daily_puzzle(date: NaiveDate) -> (u64, usize, usize) {
+    let seed = date.year() as u64 * 10000 + date.month() as u64 * 100 + date.day() as u64;
+    // puzzles get harder through the week
+    let difficulty = date.weekday().num_days_from_monday() as usize;
+    (seed, 4 + difficulty * 2 / 3, 6 + difficulty * 2)
+}
+
+impl DailyRun {
+    /// The seed and settings for this run's puzzle, which don't depend on the
+    /// player's settings at all
+    pub(crate) fn puzzle(&self) -> (u64, GenerateConfig) {
+        let (seed, num_circles, limit_nodes) = daily_puzzle(self.date);
+        let generate_config = GenerateConfig {
+            generator: Generator::Circles,
+            num_circles,
+            limit_nodes,
+            ..GenerateConfig::default()
+        };
+        (seed, generate_config)
+    }
+}
+
+/// Start a daily run, which the reset sent with it generates the puzzle for
+/// without touching the player's settings
+pub(crate) fn start_daily(
+    mut actions: EventReader<Action>,
+    mut daily: ResMut<Daily>,
+    time: Res<Time>,
+    history: Res<History>,
+    hints_used: Res<HintsUsed>,
+) {
+    let actions = actions.read().collect::<Vec<_>>();
+    // the daily is started with a reset of its own
+    let starting = actions.contains(&&Action::Daily);
+    for action in actions {
+        match action {
+            Action::Daily => {
+                daily.0 = Some(DailyRun {
+                    date: Local::now().date_naive(),
+                    started: time.elapsed_seconds_f64(),
+                    moves_before: history.moves_made(),
+                    hints_before: hints_used.0,
+                    tangled: false,
+                    result: None,
+                });
+            }
+            // changing the puzzle, or making a new one, abandons the daily one
+            Action::Reset if !starting => daily.0 = None,
+            Action::Size(..)
+            | Action::Seed(_)
+            | Action::Generator(_)
+            | Action::Difficulty(..)
+            | Action::Bigger
+            | Action::Smaller
+            | Action::Level(_)
+            | Action::Import(_) => daily.0 = None,
+            _ => {}
+        };
+    }
+}
+
+pub(crate) fn finish_daily(
+    mut daily: ResMut<Daily>,
+    intersections: Res<IntersectionsCount>,
+    time: Res<Time>,
+    history: Res<History>,
+    hints_used: Res<HintsUsed>,
+) {
+    let Some(run) = &mut daily.0 else {
+        return;
+    };
+    if run.result.is_some() {
+        return;
+    }
+    if intersections.0 > 0 {
+        run.tangled = true;
+        return;
+    }
+    if !run.tangled {
+        return;
+    }
+    let result = DailyResult {
+        seconds: time.elapsed_seconds_f64() - run.started,
+        moves: history.moves_made() - run.moves_before,
+        hints: hints_used.0 - run.hints_before,
+    };
+    run.result = Some(result);
+    eprintln!("{}", summary(run.date, &result));
+    if let Err(error) = save_result(run.date, &result) {
+        eprintln!("Couldn't save daily result: {error}");
+    }
+}
+
+/// Where daily results are kept, one line each of date, seconds, moves and
+/// hints
+fn results_path() -> Option<PathBuf> {
+    Some(
+        ProjectDirs::from("", "", "planarity")?
+            .data_dir()
+            .join("daily.csv"),
+    )
+}
+
+fn save_result(date: NaiveDate, result: &DailyResult) -> std::io::Result<()> {
+    let Some(path) = results_path() else {
+        return Ok(());
+    };
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        file,
+        "{date},{:.1},{},{}",
+        result.seconds, result.moves, result.hints
+    )
+}
+
+/// A result written out for sharing with other players
+pub(crate) fn summary(date: NaiveDate, result: &DailyResult) -> String {
+    let seconds = result.seconds as u64;
+    format!(
+        "Planarity daily {date}\nUntangled in {}:{:02}, {} moves, {} hints",
+        seconds / 60,
+        seconds % 60,
+        result.moves,
+        result.hints
+    )
+}
+
+pub(crate) fn daily_menu(
+    mut contexts: EguiContexts,
+    mut actions: EventWriter<Action>,
+    daily: Res<Daily>,
+) {
+    egui::Window::new("Menu").show(contexts.ctx_mut(), |ui| {
+        if ui.button("Daily puzzle").clicked() {
+            actions.send(Action::Daily);
+            actions.send(Action::Reset);
+        }
+        if let Some(DailyRun {
+            date,
+            result: Some(result),
+            ..
+        }) = &daily.0
+        {
+            let summary = summary(*date, result);
+            ui.label(&summary);
+            if ui.button("Copy").clicked() {
+                ui.output_mut(|output| output.copied_text = summary);
+            }
+        }
+    });
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, fmt::Write as _, fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use chrono::Local;
+use directories::ProjectDirs;
+use ron::ser::PrettyConfig;
+use thiserror::Error;
+
+use planarity_core::level::{LevelFile, LevelVertex};
+
+use crate::{input::Action, level::Pinned, Edge, Node};
+
+/// Space left round the drawing in an exported SVG
+const SVG_MARGIN: f32 = 20.;
+
+/// Radius of the nodes, as drawn by `make_node`
+const NODE_RADIUS: f32 = 10.;
+
+#[derive(Error, Debug)]
+pub(crate) enum ExportError {
+    #[error("Could not write export: {0}")]
+    Io(#[from] io::Error),
+    #[error("Could not write level: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("No directory to export to")]
+    NoDirectory,
+}
+
+/// A node as it's currently drawn
+struct ExportNode {
+    position: Vec2,
+    fill: Color,
+    /// Only pinned nodes have an outline
+    stroke: Option<(Color, f32)>,
+    pinned: bool,
+}
+
+/// The graph on screen, with nodes numbered in the order they're listed
+struct Drawing {
+    nodes: Vec<ExportNode>,
+    edges: Vec<(usize, usize, Color, f32)>,
+}
+
+pub(crate) fn export_drawing(
+    mut actions: EventReader<Action>,
+    nodes: Query<(Entity, &Transform, &Fill, Option<&Stroke>, Option<&Pinned>), With<Node>>,
+    edges: Query<(&Stroke, &Edge)>,
+) {
+    if !actions.read().any(|action| *action == Action::Export) {
+        return;
+    }
+    let mut indices = HashMap::new();
+    let nodes = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, (entity, transform, fill, stroke, pinned))| {
+            indices.insert(entity, index);
+            ExportNode {
+                position: transform.translation.truncate(),
+                fill: fill.color,
+                stroke: stroke.map(|stroke| (stroke.color, stroke.options.line_width)),
+                pinned: pinned.is_some(),
+            }
+        })
+        .collect();
+    let edges = edges
+        .iter()
+        .filter_map(|(stroke, Edge(start, end))| {
+            Some((
+                *indices.get(start)?,
+                *indices.get(end)?,
+                stroke.color,
+                stroke.options.line_width,
+            ))
+        })
+        .collect();
+    match (Drawing { nodes, edges }).save() {
+        Ok(path) => eprintln!("Exported drawing to {}", path.display()),
+        Err(error) => eprintln!("Couldn't export drawing: {error}"),
+    }
+}
+
+impl Drawing {
+    /// Write the drawing out in every format, returning the path they share
+    /// apart from the extension
+    fn save(&self) -> Result<PathBuf, ExportError> {
+        let directory = ProjectDirs::from("", "", "planarity")
+            .ok_or(ExportError::NoDirectory)?
+            .data_dir()
+            .join("exports");
+        fs::create_dir_all(&directory)?;
+        let name = Local::now().format("%Y-%m-%d-%H%M%S").to_string();
+        let path = directory.join(&name);
+        fs::write(path.with_extension("svg"), self.svg())?;
+        fs::write(path.with_extension("dot"), self.dot())?;
+        let level = ron::ser::to_string_pretty(&self.level(name), PrettyConfig::default())?;
+        fs::write(path.with_extension("level"), level)?;
+        Ok(path)
+    }
+
+    /// The drawing with the same colours as on screen
+    fn svg(&self) -> String {
+        let (min, max) = self.nodes.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), node| (min.min(node.position), max.max(node.position)),
+        );
+        let (min, max) = if self.nodes.is_empty() {
+            (Vec2::ZERO, Vec2::ZERO)
+        } else {
+            (min, max)
+        };
+        let margin = Vec2::splat(NODE_RADIUS + SVG_MARGIN);
+        let size = max - min + 2. * margin;
+        // SVG's y axis points down
+        let point = |position: Vec2| {
+            let Vec2 { x, y } = position - min + margin;
+            (x, size.y - y)
+        };
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"0 0 {} {}\">\n<rect width=\"100%\" height=\"100%\" fill=\"black\"/>\n",
+            size.x, size.y, size.x, size.y
+        );
+        for &(start, end, color, width) in &self.edges {
+            let (x1, y1) = point(self.nodes[start].position);
+            let (x2, y2) = point(self.nodes[end].position);
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"{}\" \
+                 stroke-width=\"{width}\"/>",
+                hex(color)
+            );
+        }
+        for node in &self.nodes {
+            let (x, y) = point(node.position);
+            let stroke = node.stroke.map_or_else(String::new, |(color, width)| {
+                format!(" stroke=\"{}\" stroke-width=\"{width}\"", hex(color))
+            });
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{x}\" cy=\"{y}\" r=\"{NODE_RADIUS}\" fill=\"{}\"{stroke}/>",
+                hex(node.fill)
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Graphviz, with positions that `neato -n` keeps as they are
+    fn dot(&self) -> String {
+        let mut dot = String::from("graph {\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let Vec2 { x, y } = node.position;
+            let _ = writeln!(dot, "  {index} [pos=\"{x},{y}!\"];");
+        }
+        for (start, end, ..) in &self.edges {
+            let _ = writeln!(dot, "  {start} -- {end};");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// A level starting from this layout
+    fn level(&self, title: String) -> LevelFile {
+        LevelFile {
+            title,
+            par_moves: None,
+            vertices: self
+                .nodes
+                .iter()
+                .map(|node| LevelVertex {
+                    position: node.position.into(),
+                    pinned: node.pinned,
+                })
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .map(|(start, end, ..)| (*start, *end))
+                .collect(),
+            solution: None,
+        }
+    }
+}
+
+fn hex(color: Color) -> String {
+    let [red, green, blue, _] = color.as_rgba_u8();
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
@@ -3,8 +3,8 @@ use bevy::{
     math::Vec3Swizzles,
     prelude::*,
 };
-use geo::{Closest, ClosestPoint, Line, Point};
-use itertools::Itertools;
+
+use planarity_core::forces;
 
 use crate::{config::Cfg, cursor::CursorWorldCoords, Edge, Node};
 
@@ -23,20 +23,27 @@ where
     NodeData: WorldQuery,
     EdgeData: WorldQuery,
 {
+    /// Where an edge's ends are, if they're both nodes
+    fn line(&self, Edge(start, end): &Edge) -> Option<[Vec2; 2]> {
+        let [Ok((start, _)), Ok((end, _))] = [self.nodes.get(*start), self.nodes.get(*end)] else {
+            return None;
+        };
+        Some([start.translation.xy(), end.translation.xy()])
+    }
+
     pub(crate) fn points_strength<'a>(
         &self,
         nodes: impl IntoIterator<Item = &'a Transform>,
         point: Vec2,
         base: f64,
     ) -> Vec2 {
-        let nodes = nodes
-            .into_iter()
-            .map(|transform| transform.translation.xy() - point)
-            .collect_vec();
-        softargmin(&nodes, base)
-            .iter()
-            .map(|(weight, vec)| *weight as f32 * *vec)
-            .sum()
+        forces::points_strength(
+            nodes
+                .into_iter()
+                .map(|transform| transform.translation.xy()),
+            point,
+            base,
+        )
     }
 
     pub(crate) fn lines_strength<'a>(
@@ -45,37 +52,11 @@ where
         point: Vec2,
         base: f64,
     ) -> Vec2 {
-        fn to_point(v: Vec2) -> Point<f32> {
-            Into::<[f32; 2]>::into(v).into()
-        }
-        fn to_vec(v: Point<f32>) -> Vec2 {
-            Into::<[f32; 2]>::into(v).into()
-        }
-        let geo_point = to_point(point);
-        let result = edges
-            .into_iter()
-            .map(|Edge(start, end)| {
-                let (start, end) = (
-                    to_point(self.nodes.get(*start).unwrap().0.translation.xy()),
-                    to_point(self.nodes.get(*end).unwrap().0.translation.xy()),
-                );
-                let closest = match <Line<f32>>::new(start, end).closest_point(&geo_point) {
-                    Closest::Intersection(closest) | Closest::SinglePoint(closest) => closest,
-                    Closest::Indeterminate => return Vec2::ZERO,
-                };
-                to_vec(closest) - point
-            })
-            .collect_vec();
-
-        let result = softargmin(&result, base)
-            .iter()
-            .map(|(weight, vec)| *weight as f32 * *vec)
-            .sum();
-        result
-    }
-
-    pub(crate) fn boundary_strength(&self, point: Vec2) -> Vec2 {
-        -point
+        forces::lines_strength(
+            edges.into_iter().filter_map(|edge| self.line(edge)),
+            point,
+            base,
+        )
     }
 }
 
@@ -83,26 +64,22 @@ impl<EdgeData> Field<'_, '_, Entity, EdgeData>
 where
     EdgeData: WorldQuery,
 {
-    pub(crate) fn points_strength_except(&self, entity: Entity, point: Vec2, base: f64) -> Vec2 {
-        self.points_strength(
-            self.nodes
-                .iter()
-                .filter(|(_, other_entity)| *other_entity != entity)
-                .map(|(edge, _)| edge),
-            point,
-            base,
-        )
+    /// Where every node but `entity` is
+    pub(crate) fn positions_except(&self, entity: Entity) -> Vec<Vec2> {
+        self.nodes
+            .iter()
+            .filter(|(_, other_entity)| *other_entity != entity)
+            .map(|(transform, _)| transform.translation.xy())
+            .collect()
     }
 
-    pub(crate) fn lines_strength_except(&self, entity: Entity, point: Vec2, base: f64) -> Vec2 {
-        self.lines_strength(
-            self.edges
-                .iter()
-                .filter(|(Edge(start, end), _)| *start != entity && *end != entity)
-                .map(|(edge, _)| edge),
-            point,
-            base,
-        )
+    /// Every edge that doesn't end at `entity`
+    pub(crate) fn lines_except(&self, entity: Entity) -> Vec<[Vec2; 2]> {
+        self.edges
+            .iter()
+            .filter(|(Edge(start, end), _)| *start != entity && *end != entity)
+            .filter_map(|(edge, _)| self.line(edge))
+            .collect()
     }
 }
 
@@ -120,7 +97,7 @@ pub(crate) fn show_strength(
         let red = field
             .lines_strength((&field.edges).iter().map(|x| x.0), cursor, cfg.field_base)
             .length();
-        let green = field.boundary_strength(cursor).length();
+        let green = forces::boundary_strength(cursor).length();
         eprintln!("{red} {blue} {green}");
         // clear.0 = Color::rgb(10. / red, 1. / green, 100000. / blue);
     }
@@ -130,24 +107,13 @@ pub(crate) fn debug_field(field: Field<Entity, Entity>, mut gizmos: Gizmos, cfg:
     if cfg.debug_vecs {
         for (point, entity) in &field.nodes {
             let point = point.translation.xy();
-            let nodes = field.points_strength_except(entity, point, cfg.field_base);
-            let edges = field.lines_strength_except(entity, point, cfg.field_base);
-            let bounds = field.boundary_strength(point);
+            let nodes =
+                forces::points_strength(field.positions_except(entity), point, cfg.field_base);
+            let edges = forces::lines_strength(field.lines_except(entity), point, cfg.field_base);
+            let bounds = forces::boundary_strength(point);
             gizmos.line_gradient_2d(point, point + nodes, Color::RED, Color::WHITE);
             gizmos.line_gradient_2d(point, point + edges, Color::BLUE, Color::WHITE);
             gizmos.line_gradient_2d(point, point + bounds, Color::GREEN, Color::WHITE);
         }
     }
 }
-
-pub(crate) fn softargmin(vecs: &[Vec2], sigma: f64) -> Vec<(f64, Vec2)> {
-    let mut vecs = vecs
-        .iter()
-        .map(|vec| (sigma.powf(-vec.length() as f64), *vec))
-        .collect_vec();
-    let sum: f64 = vecs.iter().map(|(weight, _)| weight).sum();
-    vecs.iter_mut().for_each(|(weight, _)| {
-        *weight /= sum;
-    });
-    vecs
-}
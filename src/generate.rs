@@ -1,96 +1,180 @@
 use crate::{
-    config::Cfg, cursor::TrackCursor, Edge, Endpoint, LevelCleanup, LineIntersects, Node, Velocity,
+    config::Cfg,
+    cursor::TrackCursor,
+    daily::{self, Daily},
+    hint::crossings_at,
+    import::{self, ImportQueue, Imported},
+    input,
+    level::{self, LevelAssetPlugin},
+    AppState, Edge, Endpoint, LevelCleanup, LineIntersects, Node, Velocity,
 };
-use bevy::prelude::*;
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_inspector_egui::{inspector_options::ReflectInspectorOptions, InspectorOptions};
 use bevy_mod_picking::prelude::*;
 use bevy_prototype_lyon::{prelude::*, shapes::Circle};
+use itertools::Itertools;
 use petgraph::{
     prelude::*,
-    stable_graph::IndexType,
     visit::{IntoEdgeReferences, IntoNodeReferences},
 };
-use rand::{
-    distributions::{Distribution, WeightedIndex},
-    thread_rng, Rng,
+pub(crate) use planarity_core::generate::{Generator, Level};
+use planarity_core::{difficulty::Difficulty, drawing::planar_drawing, generate::pick_level};
+use rand::{thread_rng, Rng};
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::TAU,
 };
-use std::f32::consts::{PI, TAU};
 
+/// Other versions of the level, to step through by making it bigger or smaller
 #[derive(Resource, Clone, Debug)]
-pub(crate) struct PreviousGraphs<N = Endpoint, E = ()>(pub Vec<StableGraph<N, E, Undirected>>);
+pub(crate) struct PreviousGraphs<N = Endpoint, E = ()> {
+    /// Finer graphs to split nodes of the level out into, finest first
+    pub bigger: Vec<StableGraph<N, E, Undirected>>,
+    /// Coarser graphs the level was split from, coarsest first
+    pub smaller: Vec<StableGraph<N, E, Undirected>>,
+}
+
+impl<N, E> Default for PreviousGraphs<N, E> {
+    fn default() -> Self {
+        Self {
+            bigger: Vec::new(),
+            smaller: Vec::new(),
+        }
+    }
+}
+
+/// The seed the current puzzle was generated from
+#[derive(Resource, Default, Reflect, Clone, Copy, Debug, InspectorOptions)]
+#[reflect(Resource, InspectorOptions, Default)]
+pub(crate) struct PuzzleSeed(pub u64);
+
+/// Where each node of the current graph goes in a drawing with no crossings
+#[derive(Resource, Clone, Debug, Default)]
+pub(crate) struct ReferenceSolution(pub HashMap<Entity, Vec2>);
+
+/// Making puzzles: generating them, changing their size, and loading levels
+/// and imported graphs
+pub struct GenerationPlugin;
+
+impl Plugin for GenerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PreviousGraphs>()
+            .init_resource::<ReferenceSolution>()
+            .init_resource::<PuzzleSeed>()
+            .init_resource::<Difficulty>()
+            .init_resource::<Daily>()
+            .init_resource::<Imported>()
+            .init_resource::<ImportQueue>()
+            .add_plugins(LevelAssetPlugin)
+            .add_systems(Startup, make_network)
+            .add_systems(Update, import::queue_dropped_files)
+            .add_systems(
+                Update,
+                (
+                    (
+                        input::network_size,
+                        input::set_seed,
+                        input::set_difficulty,
+                        daily::start_daily,
+                        input::reset_network,
+                    )
+                        .chain(),
+                    input::bigger_network,
+                    input::smaller_network,
+                    (
+                        (level::load_level, level::spawn_level).chain(),
+                        (import::send_imports, import::import_graph).chain(),
+                    ),
+                    (level::show_level_info, import::show_imported),
+                    animate_splits,
+                )
+                    .run_if(in_state(AppState::Running)),
+            );
+    }
+}
 
 pub(crate) fn make_network(
     mut commands: Commands,
     cfg: Res<Cfg>,
     mut previous_graphs: ResMut<PreviousGraphs>,
+    mut solution: ResMut<ReferenceSolution>,
+    mut puzzle_seed: ResMut<PuzzleSeed>,
+    mut difficulty: ResMut<Difficulty>,
+    daily: Res<Daily>,
 ) {
-    let mut rng = thread_rng();
-
-    let graph = make_graph(&mut rng, cfg.num_circles);
-    let graph = graph.filter_map(
-        |index, _| (graph.neighbors(index).count() > 1).then(|| ()),
-        |_, _| Some(()),
-    );
-
-    let mut position = || {
-        let random_offset = cfg.node_starting_random_offset;
-        Vec2::from_angle(rng.gen_range(0.0..TAU))
-            * if random_offset != 0. {
-                cfg.node_starting_distance + rng.gen_range((-random_offset)..random_offset)
-            } else {
-                cfg.node_starting_distance
-            }
+    let (seed, generate_config) = match &daily.0 {
+        Some(run) => run.puzzle(),
+        None => (
+            cfg.seed.unwrap_or_else(|| thread_rng().gen()),
+            cfg.generate_config(),
+        ),
     };
-    let mut graph = graph.map(
-        |_, _| {
-            let pos = position();
-            make_endpoint(&mut commands, make_node(pos), pos)
-        },
-        |_, _| (),
-    );
-
-    let previous_graphs = &mut previous_graphs.0;
-    previous_graphs.clear();
-    previous_graphs.reserve(graph.node_count().saturating_sub(cfg.limit_nodes));
-
-    fn degree_weighted_random(
-        graph: &StableGraph<Endpoint, (), Undirected>,
-        rng: &mut impl Rng,
-        nodes: impl Iterator<Item = NodeIndex>,
-    ) -> Option<NodeIndex> {
-        let nodes: Vec<_> = nodes.collect();
-        let weights: Vec<_> = nodes
-            .iter()
-            .map(|node| graph.neighbors(*node).count().ilog2())
-            .collect();
-        let distribution = WeightedIndex::new(weights).unwrap();
-        nodes.get(distribution.sample(rng)).copied()
-    }
+    puzzle_seed.0 = seed;
+    let (level, level_difficulty) = match pick_level(seed, &generate_config) {
+        Ok(level) => level,
+        Err(error) => {
+            eprintln!("Couldn't generate puzzle: {error}");
+            return;
+        }
+    };
+    *difficulty = level_difficulty;
+    spawn_level(commands, level, &cfg, &mut previous_graphs, &mut solution);
+}
 
-    while cfg.limit_nodes < graph.node_count() {
-        eprintln!("{}", graph.node_count());
-        previous_graphs.push(graph.clone());
-        let node = degree_weighted_random(
-            &graph,
-            &mut rng,
-            graph.node_references().map(|(node, _)| node),
-        )
-        .unwrap();
-        let Some(other) = degree_weighted_random(&graph, &mut rng, graph.neighbors(node)) else {
-            graph.remove_node(node).unwrap();
-            continue;
-        };
-        let Endpoint(entity, _) = graph.node_weight(other).unwrap();
-        commands
-            .entity(*entity)
-            .insert(Visibility::Hidden)
-            .remove::<Node>();
-        merge_nodes(&mut graph, node, other);
+/// Spawn the nodes and edges of a level
+pub(crate) fn spawn_level(
+    mut commands: Commands,
+    Level { graph, previous }: Level,
+    cfg: &Cfg,
+    previous_graphs: &mut PreviousGraphs,
+    solution: &mut ReferenceSolution,
+) {
+    // every node that's in any version of the level gets spawned now, but only
+    // shown once it's part of the graph
+    let all_nodes = previous.first().unwrap_or(&graph);
+    let entities: HashMap<NodeIndex, Entity> = all_nodes
+        .node_references()
+        .map(|(node, position)| (node, commands.spawn(make_node(*position)).id()))
+        .collect();
+    for node in all_nodes.node_indices() {
+        if !graph.contains_node(node) {
+            commands
+                .entity(entities[&node])
+                .insert(Visibility::Hidden)
+                .remove::<Node>();
+        }
     }
+    let with_entities = |graph: &StableGraph<Vec2, (), Undirected>| {
+        graph.map(
+            |node, position| Endpoint(entities[&node], *position),
+            |_, _| (),
+        )
+    };
+    previous_graphs.bigger = previous.iter().map(with_entities).collect();
+    previous_graphs.smaller.clear();
+    let graph = with_entities(&graph);
 
+    *solution = reference_solution(&graph, cfg.target_centre_length);
     add_edges(commands, graph);
 }
 
-fn add_edges(mut commands: Commands, graph: StableGraph<Endpoint, (), Undirected>) {
+pub(crate) fn reference_solution(
+    graph: &StableGraph<Endpoint, (), Undirected>,
+    radius: f32,
+) -> ReferenceSolution {
+    let Some(positions) = planar_drawing(graph, radius) else {
+        eprintln!("Couldn't find a reference solution for a non-planar graph");
+        return default();
+    };
+    ReferenceSolution(
+        positions
+            .into_iter()
+            .map(|(node, position)| (graph[node].0, position))
+            .collect(),
+    )
+}
+
+pub(crate) fn add_edges(mut commands: Commands, graph: StableGraph<Endpoint, (), Undirected>) {
     for edge in graph.edge_references() {
         let start = &graph[edge.source()];
         let end = &graph[edge.target()];
@@ -159,94 +243,238 @@ pub(crate) fn make_edge(
     edge
 }
 
-pub(crate) fn make_endpoint(
-    commands: &mut Commands,
-    bundle: impl Bundle,
-    position: Vec2,
-) -> Endpoint {
-    Endpoint(commands.spawn(bundle).id(), position)
+fn replace_graph(
+    mut commands: Commands,
+    graph: StableGraph<Endpoint, (), Undirected>,
+    edges: Query<(Entity, &Edge)>,
+) {
+    for (edge, _) in &edges {
+        commands.entity(edge).despawn();
+    }
+    for Endpoint(entity, _) in graph.node_weights() {
+        commands
+            .entity(*entity)
+            .insert((Node, LineIntersects::Unsolved, Visibility::Inherited));
+    }
+    add_edges(commands, graph);
+}
+
+/// How far a node moves away from the one it splits off from
+const SPLIT_DISTANCE: f32 = 40.;
+
+/// How long splitting a node takes, in seconds
+const SPLIT_SECONDS: f32 = 0.4;
+
+/// Animates a node moving out of the node it was merged into
+#[derive(Component, Debug)]
+pub(crate) struct Split {
+    from: Vec2,
+    to: Vec2,
+    timer: Timer,
 }
 
-fn make_graph(mut rng: impl Rng, num_circles: usize) -> StableUnGraph<(), ()> {
-    fn sort_pair(x: usize, y: usize) -> (usize, usize) {
-        (x.min(y), x.max(y))
+pub(crate) fn bigger_graph(
+    mut commands: Commands,
+    mut previous: ResMut<PreviousGraphs>,
+    mut solution: ResMut<ReferenceSolution>,
+    nodes: Query<(Entity, &Transform), With<Node>>,
+    edges: Query<(Entity, &Edge)>,
+    cfg: &Cfg,
+) {
+    let Some(graph) = previous.bigger.pop() else {
+        return;
+    };
+    previous.smaller.push(current_graph(&nodes, &edges));
+    *solution = reference_solution(&graph, cfg.target_centre_length);
+
+    let mut positions: HashMap<Entity, Vec2> = nodes
+        .iter()
+        .map(|(node, transform)| (node, transform.translation.xy()))
+        .collect();
+    let mut neighbours: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for (_, Edge(start, end)) in &edges {
+        neighbours.entry(*start).or_default().push(*end);
+        neighbours.entry(*end).or_default().push(*start);
     }
-    let num_nodes = num_circles * (num_circles - 1);
-    // TODO: this is wrong number
-    let num_edges = num_circles;
-    let circles = (0..num_circles)
-        .map(|_| rng.gen::<[f32; 2]>().into())
-        .collect::<Vec<Vec2>>();
-    let mut scratch_circles = circles.iter().enumerate().collect::<Vec<_>>();
-    let mut graph = UnGraphMap::with_capacity(num_nodes, num_edges);
-    // for each circle-circle pair, connect it to each of its neighbours when
-    // sorting each circle's intersections by how far round the circle they are;
-    // that is, connect intersections that are connected by an arc if there's no
-    // other intersection on that arc, including 0,0 the distance along each
-    // circle with midpoint A, of circle with midpoint B is proportional to
-    // angle OAB, so that's a proxy for sorting
-    for (i, circle) in circles.iter().enumerate() {
-        let arc_dist = |other_centre| circle.angle_between(other_centre - *circle).rem_euclid(PI);
-        // TODO: Check they're in general position
-        scratch_circles.sort_by(|(_, &x), (_, &y)| arc_dist(x).total_cmp(&arc_dist(y)));
-        // add adjacent intersections between circle i and other circles
-        // to the graph as edges
-        for [(a, a_vec), (b, b_vec)] in scratch_circles.array_windows() {
-            if a_vec == b_vec {
-                panic!("shouldn't have two intersections at the same point, {a} and {b} at {a_vec} and {b_vec}");
-            }
-            let a = sort_pair(*a, i);
-            let b = sort_pair(*b, i);
-            graph.add_edge(a, b, ());
+    for node in graph.node_indices() {
+        let Endpoint(entity, _) = graph[node];
+        if positions.contains_key(&entity) {
+            continue;
         }
+        let split_neighbours = graph
+            .neighbors(node)
+            .map(|other| graph[other].0)
+            .collect_vec();
+        // the node it was merged into kept all of its edges
+        let parent = split_neighbours
+            .iter()
+            .filter(|other| positions.contains_key(*other))
+            .find(|other| {
+                let kept = neighbours
+                    .get(*other)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                split_neighbours
+                    .iter()
+                    .all(|neighbour| neighbour == *other || kept.contains(neighbour))
+            })
+            .or_else(|| {
+                split_neighbours
+                    .iter()
+                    .find(|other| positions.contains_key(*other))
+            })
+            .copied();
+        let Some(parent) = parent else {
+            continue;
+        };
+        let from = positions[&parent];
+        positions.insert(entity, from);
+        let to = split_position(entity, from, &graph, &positions);
+        positions.insert(entity, to);
+        commands.entity(entity).insert((
+            Transform::from_translation(from.extend(1.)),
+            Split {
+                from,
+                to,
+                timer: Timer::from_seconds(SPLIT_SECONDS, TimerMode::Once),
+            },
+        ));
     }
-    // then map graph into Entities...?
-    graph
-        .into_graph()
-        .map(
-            |_node_index, _node_weight| (),
-            |_edge_index, _edge_weight| (),
-        )
-        .into()
+    replace_graph(commands, graph, edges);
 }
 
-fn merge_nodes<N, E, Ix: IndexType>(
-    graph: &mut StableGraph<N, E, Undirected, Ix>,
-    target: NodeIndex<Ix>,
-    other: NodeIndex<Ix>,
+/// Merge the nodes split off by the last [`bigger_graph`] back together
+pub(crate) fn smaller_graph(
+    mut commands: Commands,
+    mut previous: ResMut<PreviousGraphs>,
+    mut solution: ResMut<ReferenceSolution>,
+    nodes: Query<(Entity, &Transform), With<Node>>,
+    edges: Query<(Entity, &Edge)>,
+    cfg: &Cfg,
 ) {
-    while let Some((edge_index, node_index)) = graph.neighbors(other).detach().next(&graph) {
-        let edge_weight = graph.remove_edge(edge_index).unwrap();
-        if target == node_index {
-            continue;
+    let Some(graph) = previous.smaller.pop() else {
+        return;
+    };
+    previous.bigger.push(current_graph(&nodes, &edges));
+    *solution = reference_solution(&graph, cfg.target_centre_length);
+
+    let kept = graph
+        .node_weights()
+        .map(|Endpoint(entity, _)| *entity)
+        .collect::<HashSet<_>>();
+    for (entity, _) in &nodes {
+        if !kept.contains(&entity) {
+            commands
+                .entity(entity)
+                .insert(Visibility::Hidden)
+                .remove::<(Node, Split)>();
         }
-        graph.add_edge(node_index, target, edge_weight);
     }
-    graph.remove_node(other).unwrap();
+    replace_graph(commands, graph, edges);
 }
 
-fn replace_graph(
+/// The level as it is now, with nodes where they've been moved to
+fn current_graph(
+    nodes: &Query<(Entity, &Transform), With<Node>>,
+    edges: &Query<(Entity, &Edge)>,
+) -> StableGraph<Endpoint, (), Undirected> {
+    let mut graph = StableGraph::default();
+    let indices: HashMap<Entity, NodeIndex> = nodes
+        .iter()
+        .map(|(entity, transform)| {
+            let endpoint = Endpoint(entity, transform.translation.xy());
+            (entity, graph.add_node(endpoint))
+        })
+        .collect();
+    for (_, Edge(start, end)) in edges {
+        if let (Some(start), Some(end)) = (indices.get(start), indices.get(end)) {
+            graph.add_edge(*start, *end, ());
+        }
+    }
+    graph
+}
+
+/// Where next to its parent a split node crosses the fewest edges, preferring
+/// places towards its own neighbours
+fn split_position(
+    node: Entity,
+    parent: Vec2,
+    graph: &StableGraph<Endpoint, (), Undirected>,
+    positions: &HashMap<Entity, Vec2>,
+) -> Vec2 {
+    let lines = graph
+        .edge_references()
+        .filter_map(|edge| {
+            let (start, end) = (graph[edge.source()].0, graph[edge.target()].0);
+            Some((
+                Edge(start, end),
+                [*positions.get(&start)?, *positions.get(&end)?],
+            ))
+        })
+        .collect_vec();
+    let towards = lines
+        .iter()
+        .filter_map(|(Edge(start, end), [start_position, end_position])| {
+            if *start == node {
+                Some(*end_position)
+            } else if *end == node {
+                Some(*start_position)
+            } else {
+                None
+            }
+        })
+        .map(|neighbour| neighbour - parent)
+        .sum::<Vec2>()
+        .try_normalize()
+        .unwrap_or(Vec2::X);
+    // alternate either side of the preferred direction, getting further away
+    let candidates = (1..=2).flat_map(|distance| {
+        (0..12).map(move |step| {
+            let turn = (step + 1) / 2 * if step % 2 == 0 { 1 } else { -1 };
+            parent
+                + Vec2::from_angle(TAU * turn as f32 / 12.).rotate(towards)
+                    * SPLIT_DISTANCE
+                    * distance as f32
+        })
+    });
+    candidates
+        .min_by_key(|candidate| crossings_at(node, *candidate, &lines))
+        .unwrap_or(parent)
+}
+
+pub(crate) fn animate_splits(
     mut commands: Commands,
-    graph: StableGraph<Endpoint, (), Undirected>,
-    edges: Query<Entity, With<Edge>>,
+    time: Res<Time>,
+    mut nodes: Query<(Entity, &mut Transform, &mut Split)>,
 ) {
-    for edge in &edges {
-        commands.entity(edge).despawn();
-    }
-    for Endpoint(entity, _) in graph.node_weights() {
-        commands
-            .entity(*entity)
-            .insert((Node, LineIntersects::Unsolved, Visibility::Inherited));
+    for (entity, mut transform, mut split) in &mut nodes {
+        split.timer.tick(time.delta());
+        let progress = split.timer.percent();
+        // ease out, so the node springs apart and settles
+        let eased = 1. - (1. - progress).powi(3);
+        let position = split.from.lerp(split.to, eased);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        if split.timer.finished() {
+            commands.entity(entity).remove::<Split>();
+        }
     }
-    add_edges(commands, graph);
 }
 
-pub(crate) fn bigger_graph(
-    commands: Commands,
-    mut previous: ResMut<PreviousGraphs>,
-    edges: Query<Entity, With<Edge>>,
+pub(crate) fn debug_solution(
+    solution: Res<ReferenceSolution>,
+    edges: Query<&Edge>,
+    mut gizmos: Gizmos,
+    cfg: Res<Cfg>,
 ) {
-    if let Some(graph) = previous.0.pop() {
-        replace_graph(commands, graph, edges);
+    if cfg.show_solution {
+        for position in solution.0.values() {
+            gizmos.circle_2d(*position, 10.0, Color::GRAY);
+        }
+        for Edge(start, end) in &edges {
+            if let (Some(start), Some(end)) = (solution.0.get(start), solution.0.get(end)) {
+                gizmos.line_2d(*start, *end, Color::GRAY);
+            }
+        }
     }
 }
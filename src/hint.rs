@@ -0,0 +1,232 @@
+use std::{collections::HashMap, f32::consts::TAU};
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_inspector_egui::{inspector_options::ReflectInspectorOptions, InspectorOptions};
+use itertools::Itertools;
+
+use crate::{
+    crossings::{edges_cross, Crossings},
+    generate::ReferenceSolution,
+    input::Action,
+    level::Pinned,
+    Edge, Node,
+};
+
+/// A suggested move of one node to somewhere it crosses fewer edges
+#[derive(Resource, Default, Debug)]
+pub(crate) struct Hint(pub Option<(Entity, Vec2)>);
+
+/// How many hints the player has been given
+#[derive(Resource, Default, Reflect, Clone, Debug, InspectorOptions)]
+#[reflect(Resource, InspectorOptions, Default)]
+pub(crate) struct HintsUsed(pub u32);
+
+pub(crate) fn give_hint(
+    mut actions: EventReader<Action>,
+    nodes: Query<(Entity, &Transform), With<Node>>,
+    edges: Query<(Entity, &Edge)>,
+    pinned: Query<(), With<Pinned>>,
+    crossings: Res<Crossings>,
+    solution: Res<ReferenceSolution>,
+    mut hint: ResMut<Hint>,
+    mut hints_used: ResMut<HintsUsed>,
+) {
+    for action in actions.read() {
+        match action {
+            Action::Hint => {
+                let positions: HashMap<Entity, Vec2> = nodes
+                    .iter()
+                    .map(|(entity, transform)| (entity, transform.translation.xy()))
+                    .collect();
+                let lines = edges
+                    .iter()
+                    .filter_map(|(_, edge)| {
+                        Some((*edge, [*positions.get(&edge.0)?, *positions.get(&edge.1)?]))
+                    })
+                    .collect_vec();
+                let tangled = edges
+                    .iter()
+                    .filter(|(entity, _)| crossings.crosses_any(*entity))
+                    .flat_map(|(_, Edge(start, end))| [*start, *end])
+                    .filter(|node| positions.contains_key(node) && !pinned.contains(*node))
+                    .unique()
+                    .collect_vec();
+                if let Some(best) = best_move(&tangled, &positions, &lines, &solution) {
+                    hint.0 = Some(best);
+                    hints_used.0 += 1;
+                }
+                return;
+            }
+            _ => {}
+        };
+    }
+}
+
+/// The move of a single node that removes the most crossings, preferring
+/// shorter moves
+fn best_move(
+    tangled: &[Entity],
+    positions: &HashMap<Entity, Vec2>,
+    lines: &[(Edge, [Vec2; 2])],
+    solution: &ReferenceSolution,
+) -> Option<(Entity, Vec2)> {
+    let aligned = align(&solution.0, positions);
+    tangled
+        .iter()
+        .flat_map(|node| {
+            let current = crossings_at(*node, positions[node], lines);
+            candidates(*node, positions, lines, aligned.get(node).copied())
+                .into_iter()
+                .map(move |target| {
+                    let removed = current as isize - crossings_at(*node, target, lines) as isize;
+                    (removed, -positions[node].distance(target), *node, target)
+                })
+        })
+        .filter(|(removed, ..)| *removed > 0)
+        .max_by(|(a_removed, a_distance, ..), (b_removed, b_distance, ..)| {
+            a_removed
+                .cmp(b_removed)
+                .then(a_distance.total_cmp(b_distance))
+        })
+        .map(|(_, _, node, target)| (node, target))
+}
+
+/// Places worth trying to move a node to: round its neighbours, and where the
+/// reference solution puts it
+fn candidates(
+    node: Entity,
+    positions: &HashMap<Entity, Vec2>,
+    lines: &[(Edge, [Vec2; 2])],
+    reference: Option<Vec2>,
+) -> Vec<Vec2> {
+    let neighbours = lines
+        .iter()
+        .filter_map(|(Edge(start, end), _)| {
+            if *start == node {
+                positions.get(end)
+            } else if *end == node {
+                positions.get(start)
+            } else {
+                None
+            }
+        })
+        .copied()
+        .collect_vec();
+    if neighbours.is_empty() {
+        return reference.into_iter().collect();
+    }
+    let centre = neighbours.iter().copied().sum::<Vec2>() / neighbours.len() as f32;
+    let spread = (neighbours
+        .iter()
+        .map(|neighbour| neighbour.distance(centre))
+        .sum::<f32>()
+        / neighbours.len() as f32)
+        .max(20.);
+
+    let ring = (1..=2).flat_map(|distance| {
+        (0..12).map(move |step| {
+            centre + Vec2::from_angle(TAU * step as f32 / 12.) * spread * distance as f32 / 2.
+        })
+    });
+    [centre].into_iter().chain(reference).chain(ring).collect()
+}
+
+/// Number of crossings involving a node's edges if it were at `position`
+pub(crate) fn crossings_at(node: Entity, position: Vec2, lines: &[(Edge, [Vec2; 2])]) -> usize {
+    let moved = lines
+        .iter()
+        .map(|&(edge, [start, end])| {
+            let start = if edge.0 == node { position } else { start };
+            let end = if edge.1 == node { position } else { end };
+            (edge, [start, end])
+        })
+        .collect_vec();
+    let incident = |index: usize| moved[index].0 .0 == node || moved[index].0 .1 == node;
+    (0..moved.len())
+        .filter(|index| incident(*index))
+        .flat_map(|index| {
+            // pairs of incident edges are only counted once
+            (0..moved.len())
+                .filter(move |other| *other != index && !(incident(*other) && *other < index))
+                .map(move |other| (index, other))
+        })
+        .filter(|(index, other)| edges_cross(moved[*index], moved[*other]))
+        .count()
+}
+
+/// Fit the reference solution onto the current layout with a rotation,
+/// scaling and translation, so its positions make sense as targets
+fn align(
+    reference: &HashMap<Entity, Vec2>,
+    current: &HashMap<Entity, Vec2>,
+) -> HashMap<Entity, Vec2> {
+    let shared = reference
+        .iter()
+        .filter_map(|(node, reference)| Some((*node, *reference, *current.get(node)?)))
+        .collect_vec();
+    if shared.len() < 2 {
+        return HashMap::new();
+    }
+    let count = shared.len() as f32;
+    let reference_centre = shared
+        .iter()
+        .map(|(_, reference, _)| *reference)
+        .sum::<Vec2>()
+        / count;
+    let current_centre = shared.iter().map(|(_, _, current)| *current).sum::<Vec2>() / count;
+
+    // least squares fit treating points as complex numbers, where multiplying
+    // by a complex number rotates and scales
+    let (mut numerator, mut denominator) = (Vec2::ZERO, 0.);
+    for (_, reference, current) in &shared {
+        let (reference, current) = (*reference - reference_centre, *current - current_centre);
+        numerator += Vec2::new(
+            current.x * reference.x + current.y * reference.y,
+            current.y * reference.x - current.x * reference.y,
+        );
+        denominator += reference.length_squared();
+    }
+    if denominator == 0. {
+        return HashMap::new();
+    }
+    let factor = numerator / denominator;
+    shared
+        .into_iter()
+        .map(|(node, reference, _)| {
+            (
+                node,
+                current_centre + factor.rotate(reference - reference_centre),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn show_hint(
+    mut hint: ResMut<Hint>,
+    nodes: Query<&Transform, With<Node>>,
+    mut gizmos: Gizmos,
+) {
+    let Some((node, target)) = hint.0 else {
+        return;
+    };
+    let Ok(transform) = nodes.get(node) else {
+        hint.0 = None;
+        return;
+    };
+    let position = transform.translation.xy();
+    if position.distance(target) < 10. {
+        hint.0 = None;
+        return;
+    }
+    let direction = (target - position).normalize_or_zero();
+    let tip = target - direction * 10.;
+    gizmos.circle_2d(target, 10., Color::YELLOW);
+    gizmos.line_2d(position, tip, Color::YELLOW);
+    for side in [-1., 1.] {
+        gizmos.line_2d(
+            tip,
+            tip - direction * 10. + direction.perp() * side * 6.,
+            Color::YELLOW,
+        );
+    }
+}
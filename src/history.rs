@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use leafwing_input_manager::prelude::*;
+
+use crate::{cursor::TrackCursor, input::Action, Node};
+
+/// A node moving from one position to another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Move {
+    pub node: Entity,
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+/// Groups of moves that can be undone and redone together
+#[derive(Resource, Default, Debug)]
+pub(crate) struct History {
+    undo: Vec<Vec<Move>>,
+    redo: Vec<Vec<Move>>,
+    /// Every group of moves recorded, including ones since undone or cleared
+    made: u32,
+}
+
+impl History {
+    pub(crate) fn record(&mut self, moves: Vec<Move>) {
+        if !moves.is_empty() {
+            self.undo.push(moves);
+            self.redo.clear();
+            self.made += 1;
+        }
+    }
+
+    pub(crate) fn moves_made(&self) -> u32 {
+        self.made
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Record a move for each node the player picks up and puts down somewhere
+/// else
+pub(crate) fn record_drags(
+    nodes: Query<(Entity, &TrackCursor, &Transform), Changed<TrackCursor>>,
+    mut picked_up: Local<HashMap<Entity, Vec2>>,
+    mut history: ResMut<History>,
+) {
+    for (node, TrackCursor(track), transform) in &nodes {
+        let position = transform.translation.xy();
+        match (*track, picked_up.remove(&node)) {
+            (true, from) => {
+                picked_up.insert(node, from.unwrap_or(position));
+            }
+            (false, Some(from)) if from != position => history.record(vec![Move {
+                node,
+                from,
+                to: position,
+            }]),
+            (false, _) => {}
+        }
+    }
+}
+
+/// Record everything moved while [`Action::MoveOutwards`] is held as one step
+pub(crate) fn record_move_outwards(
+    actions: Res<ActionState<Action>>,
+    nodes: Query<(Entity, &Transform), With<Node>>,
+    mut before: Local<HashMap<Entity, Vec2>>,
+    mut history: ResMut<History>,
+) {
+    if actions.just_pressed(Action::MoveOutwards) {
+        *before = nodes
+            .iter()
+            .map(|(node, transform)| (node, transform.translation.xy()))
+            .collect();
+    }
+    if actions.just_released(Action::MoveOutwards) {
+        let moves = nodes
+            .iter()
+            .filter_map(|(node, transform)| {
+                let from = *before.get(&node)?;
+                let to = transform.translation.xy();
+                (from != to).then_some(Move { node, from, to })
+            })
+            .collect();
+        before.clear();
+        history.record(moves);
+    }
+}
+
+pub(crate) fn undo_redo(
+    mut actions: EventReader<Action>,
+    mut history: ResMut<History>,
+    mut nodes: Query<&mut Transform, With<Node>>,
+) {
+    for action in actions.read() {
+        match action {
+            Action::Undo => {
+                if let Some(moves) = history.undo.pop() {
+                    for Move { node, from, .. } in &moves {
+                        move_node(&mut nodes, *node, *from);
+                    }
+                    history.redo.push(moves);
+                }
+            }
+            Action::Redo => {
+                if let Some(moves) = history.redo.pop() {
+                    for Move { node, to, .. } in &moves {
+                        move_node(&mut nodes, *node, *to);
+                    }
+                    history.undo.push(moves);
+                }
+            }
+            // every node is replaced, so none of the moves mean anything now
+            Action::Reset | Action::Level(_) | Action::Import(_) => history.clear(),
+            _ => {}
+        };
+    }
+}
+
+/// Move a node, skipping nodes that have since been despawned or hidden
+fn move_node(nodes: &mut Query<&mut Transform, With<Node>>, node: Entity, position: Vec2) {
+    if let Ok(mut transform) = nodes.get_mut(node) {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
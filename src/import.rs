@@ -0,0 +1,652 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    vec,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use petgraph::prelude::*;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use thiserror::Error;
+
+use planarity_core::{difficulty::Difficulty, planarity::is_planar};
+
+use crate::{
+    config::Cfg,
+    generate::{spawn_level, Level, PreviousGraphs, ReferenceSolution},
+    input::Action,
+    LevelCleanup,
+};
+
+/// Files waiting to be imported, from the command line or dropped on the
+/// window
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ImportQueue(pub Vec<PathBuf>);
+
+/// The file the level was imported from, and whether it can be solved
+#[derive(Resource, Default, Debug)]
+pub(crate) struct Imported(pub Option<(String, bool)>);
+
+#[derive(Error, Debug)]
+pub(crate) enum ImportError {
+    #[error("Could not read graph: {0}")]
+    Io(#[from] io::Error),
+    #[error("Could not parse {format} on line {line}: {message}")]
+    Syntax {
+        format: &'static str,
+        line: usize,
+        message: String,
+    },
+    #[error("Graph has no edges")]
+    Empty,
+}
+
+/// Read a graph, guessing its format from the file extension
+pub(crate) fn read_graph(path: &Path) -> Result<StableUnGraph<(), ()>, ImportError> {
+    let text = fs::read_to_string(path)?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let graph = match extension.as_str() {
+        "dot" | "gv" => parse_dot(&text)?,
+        "graphml" | "xml" => parse_graphml(&text)?,
+        "gml" => parse_gml(&text)?,
+        _ => parse_edge_list(&text),
+    };
+    if graph.edge_count() == 0 {
+        return Err(ImportError::Empty);
+    }
+    Ok(graph)
+}
+
+/// Collects a simple graph from named nodes, ignoring loops and repeated edges
+#[derive(Default)]
+struct GraphBuilder {
+    graph: StableUnGraph<(), ()>,
+    names: HashMap<String, NodeIndex>,
+}
+
+impl GraphBuilder {
+    fn node(&mut self, name: &str) -> NodeIndex {
+        if let Some(node) = self.names.get(name) {
+            return *node;
+        }
+        let node = self.graph.add_node(());
+        self.names.insert(name.to_owned(), node);
+        node
+    }
+
+    fn edge(&mut self, start: &str, end: &str) {
+        let (start, end) = (self.node(start), self.node(end));
+        if start != end && self.graph.find_edge(start, end).is_none() {
+            self.graph.add_edge(start, end, ());
+        }
+    }
+}
+
+/// One edge per line as a pair of node names, with `#` and `%` comments
+fn parse_edge_list(text: &str) -> StableUnGraph<(), ()> {
+    let mut builder = GraphBuilder::default();
+    for line in text.lines() {
+        let line = line.split(['#', '%']).next().unwrap_or_default();
+        let mut names = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|name| !name.is_empty());
+        match (names.next(), names.next()) {
+            (Some(start), Some(end)) => builder.edge(start, end),
+            (Some(node), None) => {
+                builder.node(node);
+            }
+            _ => {}
+        }
+    }
+    builder.graph
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DotToken {
+    Id(String),
+    EdgeOp,
+    Open(char),
+    Close(char),
+    Equals,
+    Separator,
+}
+
+fn dot_tokens(text: &str) -> Result<Vec<(usize, DotToken)>, ImportError> {
+    let syntax = |line, message: &str| ImportError::Syntax {
+        format: "DOT",
+        line,
+        message: message.to_owned(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|c| *c == '\n');
+                line += 1;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut previous = ' ';
+                loop {
+                    let Some(c) = chars.next() else {
+                        return Err(syntax(start, "unclosed comment"));
+                    };
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '#' => {
+                chars.by_ref().find(|c| *c == '\n');
+                line += 1;
+            }
+            '-' if matches!(chars.peek(), Some('-' | '>')) => {
+                chars.next();
+                tokens.push((line, DotToken::EdgeOp));
+            }
+            '{' | '[' => tokens.push((line, DotToken::Open(c))),
+            '}' | ']' => tokens.push((line, DotToken::Close(c))),
+            '=' => tokens.push((line, DotToken::Equals)),
+            ';' | ',' => tokens.push((line, DotToken::Separator)),
+            '"' => {
+                let start = line;
+                let mut id = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => id.extend(chars.next()),
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            id.push(c)
+                        }
+                        None => return Err(syntax(start, "unclosed string")),
+                    }
+                }
+                tokens.push((line, DotToken::Id(id)));
+            }
+            c => {
+                let mut id = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || "_.:".contains(*c)) {
+                    id.push(c);
+                }
+                // ports and compass points are on the node, as far as we care
+                let id = id.split(':').next().unwrap_or_default().to_owned();
+                tokens.push((line, DotToken::Id(id)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Graphviz files, with subgraphs flattened and attributes ignored. An edge to
+/// or from a subgraph joins every node in it.
+fn parse_dot(text: &str) -> Result<StableUnGraph<(), ()>, ImportError> {
+    let mut parser = DotParser {
+        tokens: dot_tokens(text)?.into_iter().peekable(),
+        builder: GraphBuilder::default(),
+    };
+    // skip the header up to the opening brace
+    for (_, token) in parser.tokens.by_ref() {
+        if token == DotToken::Open('{') {
+            break;
+        }
+    }
+    parser.statements()?;
+    Ok(parser.builder.graph)
+}
+
+struct DotParser {
+    tokens: Peekable<vec::IntoIter<(usize, DotToken)>>,
+    builder: GraphBuilder,
+}
+
+impl DotParser {
+    fn syntax(line: usize, message: &str) -> ImportError {
+        ImportError::Syntax {
+            format: "DOT",
+            line,
+            message: message.to_owned(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&DotToken> {
+        self.tokens.peek().map(|(_, token)| token)
+    }
+
+    /// Statements up to the closing brace of the graph or subgraph they're
+    /// in, returning every node they mention
+    fn statements(&mut self) -> Result<Vec<String>, ImportError> {
+        let mut nodes = Vec::new();
+        loop {
+            let Some((line, token)) = self.tokens.peek().cloned() else {
+                return Ok(nodes);
+            };
+            match token {
+                DotToken::Close('}') => {
+                    self.tokens.next();
+                    return Ok(nodes);
+                }
+                DotToken::Separator => {
+                    self.tokens.next();
+                }
+                DotToken::Id(id)
+                    if ["graph", "node", "edge"].contains(&id.to_ascii_lowercase().as_str()) =>
+                {
+                    // default attributes, like `node [shape=box]`
+                    self.tokens.next();
+                    self.skip_attributes();
+                }
+                DotToken::Id(_) | DotToken::Open('{') => {
+                    self.tokens.next();
+                    if self.peek() == Some(&DotToken::Equals) {
+                        // an attribute like `rankdir = LR`
+                        self.tokens.next();
+                        self.tokens.next();
+                        continue;
+                    }
+                    let mut left = self.operand(line, token)?;
+                    while self.peek() == Some(&DotToken::EdgeOp) {
+                        self.tokens.next();
+                        let Some((line, token)) = self.tokens.next() else {
+                            return Err(Self::syntax(line, "edge with nothing after it"));
+                        };
+                        let right = self.operand(line, token)?;
+                        for start in &left {
+                            for end in &right {
+                                self.builder.edge(start, end);
+                            }
+                        }
+                        nodes.append(&mut left);
+                        left = right;
+                    }
+                    nodes.append(&mut left);
+                    self.skip_attributes();
+                }
+                DotToken::EdgeOp => return Err(Self::syntax(line, "edge with nothing before it")),
+                _ => return Err(Self::syntax(line, "unexpected symbol")),
+            }
+        }
+    }
+
+    /// The nodes at one end of an edge: a node, or everything in a subgraph
+    fn operand(&mut self, line: usize, token: DotToken) -> Result<Vec<String>, ImportError> {
+        match token {
+            DotToken::Id(id) if id.eq_ignore_ascii_case("subgraph") => {
+                if matches!(self.peek(), Some(DotToken::Id(_))) {
+                    self.tokens.next();
+                }
+                match self.tokens.next() {
+                    Some((_, DotToken::Open('{'))) => self.statements(),
+                    _ => Err(Self::syntax(line, "subgraph without a body")),
+                }
+            }
+            DotToken::Id(id) => {
+                self.builder.node(&id);
+                Ok(vec![id])
+            }
+            DotToken::Open('{') => self.statements(),
+            _ => Err(Self::syntax(line, "expected a node or subgraph")),
+        }
+    }
+
+    fn skip_attributes(&mut self) {
+        while self.peek() == Some(&DotToken::Open('[')) {
+            for (_, token) in self.tokens.by_ref() {
+                if token == DotToken::Close(']') {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Every `<name ...>` tag in an XML document, without the angle brackets
+fn xml_tags<'a>(text: &'a str, name: &'a str) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    text.match_indices('<').filter_map(move |(start, _)| {
+        let tag = &text[start + 1..];
+        let rest = tag.strip_prefix(name)?;
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+            return None;
+        }
+        let line = text[..start].matches('\n').count() + 1;
+        Some((line, &tag[..tag.find('>')?]))
+    })
+}
+
+fn xml_attribute(tag: &str, key: &str) -> Option<String> {
+    tag.match_indices(key).find_map(|(start, _)| {
+        let before = tag[..start].chars().last()?;
+        let rest = tag[start + key.len()..]
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &rest[1..];
+        // so that `id` isn't found inside `xml:id`
+        if !before.is_whitespace() {
+            return None;
+        }
+        Some(value[..value.find(quote)?].to_owned())
+    })
+}
+
+fn parse_graphml(text: &str) -> Result<StableUnGraph<(), ()>, ImportError> {
+    let mut builder = GraphBuilder::default();
+    for (line, tag) in xml_tags(text, "node") {
+        let id = xml_attribute(tag, "id").ok_or_else(|| ImportError::Syntax {
+            format: "GraphML",
+            line,
+            message: "node without an id".to_owned(),
+        })?;
+        builder.node(&id);
+    }
+    for (line, tag) in xml_tags(text, "edge") {
+        let (Some(source), Some(target)) =
+            (xml_attribute(tag, "source"), xml_attribute(tag, "target"))
+        else {
+            return Err(ImportError::Syntax {
+                format: "GraphML",
+                line,
+                message: "edge without a source and target".to_owned(),
+            });
+        };
+        builder.edge(&source, &target);
+    }
+    Ok(builder.graph)
+}
+
+/// Graph Modelling Language files, reading just node ids and edge ends
+fn parse_gml(text: &str) -> Result<StableUnGraph<(), ()>, ImportError> {
+    let syntax = |line, message: &str| ImportError::Syntax {
+        format: "GML",
+        line,
+        message: message.to_owned(),
+    };
+    let mut tokens = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            rest = &rest[start..];
+            if rest.starts_with('#') {
+                break;
+            }
+            let end = if let Some(quoted) = rest.strip_prefix('"') {
+                quoted
+                    .find('"')
+                    .ok_or_else(|| syntax(index + 1, "unclosed string"))?
+                    + 2
+            } else if rest.starts_with(['[', ']']) {
+                1
+            } else {
+                rest.find(|c: char| c.is_whitespace() || c == '[' || c == ']')
+                    .unwrap_or(rest.len())
+            };
+            tokens.push((index + 1, rest[..end].trim_matches('"')));
+            rest = &rest[end..];
+        }
+    }
+
+    let mut builder = GraphBuilder::default();
+    let mut lists: Vec<(&str, HashMap<&str, &str>)> = Vec::new();
+    let mut tokens = tokens.into_iter();
+    while let Some((line, token)) = tokens.next() {
+        if token == "]" {
+            let Some((key, values)) = lists.pop() else {
+                return Err(syntax(line, "unmatched ]"));
+            };
+            match key {
+                "node" => {
+                    let id = values
+                        .get("id")
+                        .ok_or_else(|| syntax(line, "node without an id"))?;
+                    builder.node(id);
+                }
+                "edge" => {
+                    let (Some(source), Some(target)) = (values.get("source"), values.get("target"))
+                    else {
+                        return Err(syntax(line, "edge without a source and target"));
+                    };
+                    builder.edge(source, target);
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let Some((_, value)) = tokens.next() else {
+            return Err(syntax(line, "key without a value"));
+        };
+        if value == "[" {
+            lists.push((token, HashMap::new()));
+        } else if let Some((_, values)) = lists.last_mut() {
+            values.insert(token, value);
+        }
+    }
+    Ok(builder.graph)
+}
+
+pub(crate) fn queue_dropped_files(
+    mut drops: EventReader<FileDragAndDrop>,
+    mut queue: ResMut<ImportQueue>,
+) {
+    for drop in drops.read() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = drop {
+            queue.0.push(path_buf.clone());
+        }
+    }
+}
+
+pub(crate) fn send_imports(mut queue: ResMut<ImportQueue>, mut actions: EventWriter<Action>) {
+    for path in queue.0.drain(..) {
+        actions.send(Action::Import(path));
+    }
+}
+
+pub(crate) fn import_graph(
+    mut commands: Commands,
+    mut actions: EventReader<Action>,
+    level_entities: Query<Entity, With<LevelCleanup>>,
+    cfg: Res<Cfg>,
+    mut imported: ResMut<Imported>,
+    mut previous_graphs: ResMut<PreviousGraphs>,
+    mut solution: ResMut<ReferenceSolution>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    for action in actions.read() {
+        match action {
+            Action::Import(path) => {
+                let graph = match read_graph(path) {
+                    Ok(graph) => graph,
+                    Err(error) => {
+                        eprintln!("Couldn't import {}: {error}", path.display());
+                        continue;
+                    }
+                };
+                let planar = is_planar(&graph);
+                // laid out from the seed like a generated puzzle, so an import
+                // with a seed set always starts the same way
+                let mut rng =
+                    ChaCha8Rng::seed_from_u64(cfg.seed.unwrap_or_else(|| thread_rng().gen()));
+                let mut level = Level::scattered(&graph, &mut rng, &cfg.generate_config());
+                if planar {
+                    level.tangle(&mut rng, cfg.min_crossings);
+                } else {
+                    eprintln!(
+                        "{} isn't planar, so the best that can be done is fewer crossings",
+                        path.display()
+                    );
+                }
+                *difficulty = Difficulty::of(&level.graph);
+                for entity in &level_entities {
+                    commands.entity(entity).despawn();
+                }
+                spawn_level(commands, level, &cfg, &mut previous_graphs, &mut solution);
+                let name = path.file_name().map_or_else(
+                    || path.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                );
+                imported.0 = Some((name, planar));
+                return;
+            }
+            // anything else replaces the imported graph
+            Action::Reset | Action::Level(_) => imported.0 = None,
+            _ => {}
+        };
+    }
+}
+
+pub(crate) fn show_imported(mut contexts: EguiContexts, imported: Res<Imported>) {
+    let Some((name, planar)) = &imported.0 else {
+        return;
+    };
+    egui::Window::new("Imported graph").show(contexts.ctx_mut(), |ui| {
+        ui.heading(name);
+        if !planar {
+            ui.label(
+                "This graph isn't planar, so some crossings can never be untangled. \
+                 Get them as low as you can.",
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The edges of a graph as pairs of the order their nodes were first seen
+    fn edges(graph: &StableUnGraph<(), ()>) -> Vec<(usize, usize)> {
+        let mut edges = graph
+            .edge_indices()
+            .map(|edge| {
+                let (a, b) = graph.edge_endpoints(edge).unwrap();
+                (a.index().min(b.index()), a.index().max(b.index()))
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn reads_edge_lists() {
+        let graph = parse_edge_list("# a triangle\na b\nb,c % and a comment\nc a\nb a\nd\n\n");
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(edges(&graph), [(0, 1), (0, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn reads_dot() {
+        let graph = parse_dot(
+            r#"strict graph "example" {
+                rankdir = LR
+                node [shape=circle, label="x"]
+                // a path, then a node on its own
+                a -- b -- c [color=red];
+                d /* with a comment */
+                "c" -- a:n
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(edges(&graph), [(0, 1), (0, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn dot_edges_join_every_node_of_a_subgraph() {
+        let graph =
+            parse_dot("graph { a -- {b c}; subgraph s { d e } -- f; {g} -- {h i} }").unwrap();
+        assert_eq!(graph.node_count(), 9);
+        assert_eq!(
+            edges(&graph),
+            [(0, 1), (0, 2), (3, 5), (4, 5), (6, 7), (6, 8)]
+        );
+        // nested subgraphs and chains through them
+        let graph = parse_dot("digraph { a -> { b { c } } -> d }").unwrap();
+        assert_eq!(edges(&graph), [(0, 1), (0, 2), (1, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn dot_errors_give_the_line() {
+        for (text, line) in [
+            ("graph {\n  -- a\n}", 2),
+            ("graph {\n  a --\n", 2),
+            ("graph {\n a -- b\n /* never closed\n}", 3),
+            ("graph {\n a -- ]\n}", 2),
+        ] {
+            match parse_dot(text) {
+                Err(ImportError::Syntax { line: found, .. }) => assert_eq!(found, line, "{text}"),
+                other => panic!("{text} gave {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn reads_graphml() {
+        let graph = parse_graphml(
+            r#"<?xml version="1.0"?>
+            <graphml>
+              <graph edgedefault="undirected">
+                <node id="n0"/>
+                <node id='n1'></node>
+                <node id="n2"><data key="label">x</data></node>
+                <edge id="e0" source="n0" target="n1"/>
+                <edge source="n1" target="n2"/>
+                <edge source="n2" target="n0"/>
+              </graph>
+            </graphml>"#,
+        )
+        .unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(edges(&graph), [(0, 1), (0, 2), (1, 2)]);
+
+        assert!(matches!(
+            parse_graphml("<graphml>\n<edge source=\"a\"/>\n</graphml>"),
+            Err(ImportError::Syntax { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn reads_gml() {
+        let graph = parse_gml(
+            r#"graph [
+              # a comment
+              directed 0
+              node [ id 1 label "one" graphics [ x 1.0 y 2.0 ] ]
+              node [ id 2 ]
+              node [ id 3 ]
+              edge [ source 1 target 2 ]
+              edge [ source 2 target 3 label "a [bracket]" ]
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(edges(&graph), [(0, 1), (1, 2)]);
+
+        assert!(matches!(
+            parse_gml("graph [\n edge [ source 1 ]\n]"),
+            Err(ImportError::Syntax { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_gml("graph [ ]\n]"),
+            Err(ImportError::Syntax { line: 2, .. })
+        ));
+    }
+}
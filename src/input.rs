@@ -1,21 +1,122 @@
+use std::path::PathBuf;
+
+use planarity_core::{difficulty::Difficulty, forces};
+
 use crate::{
-    cmp_vec,
+    apply_velocity, cli,
     config::Cfg,
+    crossings::{self, Crossings},
+    cursor,
+    daily::{self, Daily},
+    export,
     fields::Field,
-    generate::{bigger_graph, make_network, PreviousGraphs},
-    Edge, LevelCleanup, Node, Velocity,
+    generate::{
+        bigger_graph, make_network, smaller_graph, Generator, PreviousGraphs, PuzzleSeed,
+        ReferenceSolution,
+    },
+    highlight_edges,
+    hint::{self, Hint, HintsUsed},
+    history::{self, History},
+    level::Pinned,
+    AppState, Edge, LevelCleanup, Node, Velocity,
 };
 use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_mod_picking::{picking_core::CorePlugin, prelude::*};
 use bevy_prototype_lyon::prelude::*;
 use leafwing_input_manager::prelude::*;
 
+/// Playing a puzzle: the camera, dragging nodes, highlighting crossings, hints,
+/// undo and the daily puzzle
+#[derive(Clone)]
+pub struct InteractionPlugin {
+    /// Which keys send which actions
+    pub input_map: InputMap<Action>,
+}
+
+impl Default for InteractionPlugin {
+    fn default() -> Self {
+        Self {
+            input_map: InputMap::new([
+                (KeyCode::Space, Action::Reset),
+                (KeyCode::B, Action::Bigger),
+                (KeyCode::S, Action::Smaller),
+                (KeyCode::F, Action::MoveOutwards),
+                (KeyCode::H, Action::Hint),
+                (KeyCode::Z, Action::Undo),
+                (KeyCode::Y, Action::Redo),
+                (KeyCode::E, Action::Export),
+                (KeyCode::F5, Action::Save),
+            ]),
+        }
+    }
+}
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        // the host app may already be using picking
+        if !app.is_plugin_added::<CorePlugin>() {
+            app.add_plugins(DefaultPickingPlugins);
+        }
+        app.insert_resource(self.input_map.clone())
+            .init_resource::<ActionState<Action>>()
+            .init_resource::<Crossings>()
+            .init_resource::<Hint>()
+            .init_resource::<HintsUsed>()
+            .init_resource::<History>()
+            .add_plugins(InputManagerPlugin::<Action>::default())
+            .add_systems(Startup, cursor::setup_camera)
+            .add_systems(
+                Update,
+                (
+                    (
+                        (history::record_drags, history::record_move_outwards),
+                        (cursor::update_cursor, move_points_outwards).chain(),
+                        cursor::track_cursor,
+                        apply_velocity,
+                        move_line,
+                    )
+                        .chain(),
+                    (
+                        crossings::update_crossings,
+                        highlight_edges,
+                        daily::finish_daily,
+                    )
+                        .chain(),
+                    keyboard_action_events,
+                    (hint::give_hint, hint::show_hint).chain(),
+                    (history::undo_redo, export::export_drawing),
+                    daily::daily_menu,
+                    cli::send_start_actions,
+                )
+                    .run_if(in_state(AppState::Running)),
+            );
+    }
+}
+
 #[derive(Debug, Actionlike, Reflect, Clone, Event, PartialEq, Eq)]
 #[non_exhaustive]
-pub(crate) enum Action {
+pub enum Action {
     Reset,
     Bigger,
+    Smaller,
     MoveOutwards,
+    Hint,
+    Undo,
+    Redo,
     Size(usize, usize),
+    Seed(Option<u64>),
+    Generator(Generator),
+    /// Only make puzzles with a difficulty score in this range
+    Difficulty(u32, u32),
+    /// Load a hand-made level from `assets/levels`
+    Level(String),
+    /// Play a graph read from a file
+    Import(PathBuf),
+    /// Save the drawing as it is now
+    Export,
+    /// Save the puzzle to carry on with later
+    Save,
+    Daily,
 }
 
 pub(crate) fn keyboard_action_events(
@@ -29,48 +130,22 @@ pub(crate) fn keyboard_action_events(
 
 pub(crate) fn move_points_outwards(
     actions: Res<ActionState<Action>>,
-    mut points: Query<(Entity, &mut Velocity), With<Node>>,
+    mut points: Query<(Entity, &mut Velocity), (With<Node>, Without<Pinned>)>,
     field: Field<Entity, ()>,
     time: Res<Time>,
     cfg: Res<Cfg>,
 ) {
-    // fn target(vec: Vec2, length: f32) -> Vec2 {
-    //     with_length(vec, vec.length() - length)
-    // }
-    // fn invert(vec: Vec2) -> Vec2 {
-    //     with_length(vec, 10. / vec.length().max(1.))
-    // }
-    fn invert(value: f32) -> f32 {
-        10. / value.max(1.)
-    }
-    fn with_length(vec: Vec2, length: f32) -> Vec2 {
-        vec.normalize_or_zero() * length
-    }
-    fn distance_to_circle(vec: Vec2, radius: f32) -> f32 {
-        radius - vec.length()
-    }
     if actions.pressed(Action::MoveOutwards) {
         let delta_time = time.delta_seconds().min(0.1);
         for (entity, mut velocity) in &mut points {
             let (transform, _) = field.nodes.get(entity).unwrap();
-            let point = transform.translation.xy();
-
-            let to_nearest_point = field.points_strength_except(entity, point, cfg.field_base);
-            let to_nearest_line = field.lines_strength_except(entity, point, cfg.field_base);
-            let to_centre = field.boundary_strength(point);
-            // let target_centre = target(to_centre, cfg.target_centre_length);
-            let direction = [
-                with_length(
-                    to_centre,
-                    invert(distance_to_circle(to_centre, cfg.target_centre_length)),
-                ),
-                with_length(-to_nearest_point, invert(to_nearest_point.length() / 2.)),
-                with_length(-to_nearest_line, invert(to_nearest_line.length())),
-            ]
-            .into_iter()
-            .max_by(cmp_vec)
-            .unwrap()
-            .clamp_length_max(3.0);
+            let direction = forces::outward_direction(
+                transform.translation.xy(),
+                field.positions_except(entity),
+                field.lines_except(entity),
+                cfg.field_base,
+                cfg.target_centre_length,
+            );
             let new_velocity = delta_time * cfg.move_speed * direction;
 
             *velocity = Velocity(Some(new_velocity));
@@ -84,6 +159,10 @@ pub(crate) fn reset_network(
     level: Query<Entity, With<LevelCleanup>>,
     cfg: Res<Cfg>,
     previous_graphs: ResMut<PreviousGraphs>,
+    solution: ResMut<ReferenceSolution>,
+    puzzle_seed: ResMut<PuzzleSeed>,
+    difficulty: ResMut<Difficulty>,
+    daily: Res<Daily>,
 ) {
     for action in actions.read() {
         match action {
@@ -91,7 +170,15 @@ pub(crate) fn reset_network(
                 for entity in &level {
                     commands.get_entity(entity).unwrap().despawn();
                 }
-                return make_network(commands, cfg, previous_graphs);
+                return make_network(
+                    commands,
+                    cfg,
+                    previous_graphs,
+                    solution,
+                    puzzle_seed,
+                    difficulty,
+                    daily,
+                );
             }
             _ => {}
         };
@@ -102,32 +189,74 @@ pub(crate) fn bigger_network(
     commands: Commands,
     mut actions: EventReader<Action>,
     previous_graphs: ResMut<PreviousGraphs>,
-    edges: Query<Entity, With<Edge>>,
+    solution: ResMut<ReferenceSolution>,
+    nodes: Query<(Entity, &Transform), With<Node>>,
+    edges: Query<(Entity, &Edge)>,
+    cfg: Res<Cfg>,
 ) {
     for action in actions.read() {
         match action {
-            Action::Bigger => return bigger_graph(commands, previous_graphs, edges),
+            Action::Bigger => {
+                return bigger_graph(commands, previous_graphs, solution, nodes, edges, &cfg)
+            }
             _ => {}
         };
     }
 }
 
-pub(crate) fn network_size(
+pub(crate) fn smaller_network(
+    commands: Commands,
     mut actions: EventReader<Action>,
-    mut cfg: ResMut<Cfg>,
+    previous_graphs: ResMut<PreviousGraphs>,
+    solution: ResMut<ReferenceSolution>,
+    nodes: Query<(Entity, &Transform), With<Node>>,
+    edges: Query<(Entity, &Edge)>,
+    cfg: Res<Cfg>,
 ) {
     for action in actions.read() {
         match action {
-            Action::Size(graph_size, number_of_circles) => {
-                cfg.num_circles = *number_of_circles;
-                cfg.limit_nodes = *graph_size;
-                return;
+            Action::Smaller => {
+                return smaller_graph(commands, previous_graphs, solution, nodes, edges, &cfg)
             }
             _ => {}
         };
     }
 }
 
+// actions only change the settings for this run, so they bypass the change
+// detection that saves settings
+
+pub(crate) fn network_size(mut actions: EventReader<Action>, mut cfg: ResMut<Cfg>) {
+    let cfg = cfg.bypass_change_detection();
+    for action in actions.read() {
+        if let Action::Size(graph_size, number_of_circles) = action {
+            cfg.num_circles = *number_of_circles;
+            cfg.limit_nodes = *graph_size;
+        }
+    }
+}
+
+pub(crate) fn set_seed(mut actions: EventReader<Action>, mut cfg: ResMut<Cfg>) {
+    let cfg = cfg.bypass_change_detection();
+    for action in actions.read() {
+        match action {
+            Action::Seed(seed) => cfg.seed = *seed,
+            Action::Generator(generator) => cfg.generator = *generator,
+            _ => {}
+        };
+    }
+}
+
+pub(crate) fn set_difficulty(mut actions: EventReader<Action>, mut cfg: ResMut<Cfg>) {
+    let cfg = cfg.bypass_change_detection();
+    for action in actions.read() {
+        if let Action::Difficulty(min, max) = action {
+            cfg.min_difficulty = *min as f32;
+            cfg.max_difficulty = *max as f32;
+        }
+    }
+}
+
 pub(crate) fn move_line(
     nodes: Query<&Transform, With<Node>>,
     mut edges: Query<(&mut Path, &Edge)>,
@@ -0,0 +1,176 @@
+use std::{collections::HashMap, io};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use bevy_egui::{egui, EguiContexts};
+use bevy_prototype_lyon::prelude::*;
+use thiserror::Error;
+
+use planarity_core::{
+    difficulty::Difficulty,
+    level::{LevelFile, LevelFileError},
+};
+
+use crate::{
+    config::Cfg,
+    generate::{add_edges, make_node, reference_solution, PreviousGraphs, ReferenceSolution},
+    input::Action,
+    Endpoint, LevelCleanup,
+};
+
+/// A hand-made puzzle, loaded from a `.level` file in RON
+#[derive(Asset, TypePath, Deref, Debug, Clone)]
+pub struct LevelAsset(pub LevelFile);
+
+/// Tags nodes that stay where the level put them
+#[derive(Debug, Component)]
+pub(crate) struct Pinned;
+
+/// The title and par of the hand-made level being played, if it is one
+#[derive(Resource, Default, Debug)]
+pub(crate) struct LevelInfo(pub Option<(String, Option<u32>)>);
+
+/// A level that's been asked for but hasn't finished loading
+#[derive(Resource, Default, Debug)]
+pub(crate) struct LoadingLevel(pub Option<Handle<LevelAsset>>);
+
+pub struct LevelAssetPlugin;
+
+impl Plugin for LevelAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<LevelLoader>()
+            .init_asset::<LevelAsset>()
+            .init_resource::<LevelInfo>()
+            .init_resource::<LoadingLevel>();
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct LevelLoader;
+
+#[derive(Error, Debug)]
+pub enum LevelAssetError {
+    #[error("Could not load level: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Level(#[from] LevelFileError),
+}
+
+impl AssetLoader for LevelLoader {
+    type Asset = LevelAsset;
+    type Error = LevelAssetError;
+    type Settings = ();
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(LevelAsset(LevelFile::parse(&bytes)?))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+pub(crate) fn load_level(
+    mut actions: EventReader<Action>,
+    server: Res<AssetServer>,
+    mut loading: ResMut<LoadingLevel>,
+    mut info: ResMut<LevelInfo>,
+) {
+    for action in actions.read() {
+        match action {
+            Action::Level(name) => {
+                loading.0 = Some(server.load(format!("levels/{name}.level")));
+            }
+            // generated and imported puzzles don't have a title or par
+            Action::Reset | Action::Import(_) => info.0 = None,
+            _ => {}
+        };
+    }
+}
+
+pub(crate) fn spawn_level(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    levels: Res<Assets<LevelAsset>>,
+    mut loading: ResMut<LoadingLevel>,
+    level_entities: Query<Entity, With<LevelCleanup>>,
+    cfg: Res<Cfg>,
+    mut info: ResMut<LevelInfo>,
+    mut previous_graphs: ResMut<PreviousGraphs>,
+    mut solution: ResMut<ReferenceSolution>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    let Some(handle) = &loading.0 else {
+        return;
+    };
+    let Some(level) = levels.get(handle) else {
+        if server.get_load_state(handle) == Some(LoadState::Failed) {
+            eprintln!("Couldn't load level {:?}", handle.path());
+            loading.0 = None;
+        }
+        return;
+    };
+
+    for entity in &level_entities {
+        commands.entity(entity).despawn();
+    }
+    let graph = level.graph();
+    *difficulty = Difficulty::of(&graph);
+
+    let entities = level
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let mut node = commands.spawn(make_node(vertex.position.into()));
+            if vertex.pinned {
+                node.insert((Pinned, Stroke::new(Color::GRAY, 3.)));
+            }
+            node.id()
+        })
+        .collect::<Vec<_>>();
+    let graph = graph.map(
+        |node, position| Endpoint(entities[node.index()], *position),
+        |_, _| (),
+    );
+
+    *solution = match &level.solution {
+        Some(positions) => ReferenceSolution(
+            entities
+                .iter()
+                .zip(positions)
+                .map(|(entity, position)| (*entity, Vec2::from(*position)))
+                .collect::<HashMap<_, _>>(),
+        ),
+        None => reference_solution(&graph, cfg.target_centre_length),
+    };
+    previous_graphs.bigger.clear();
+    previous_graphs.smaller.clear();
+    info.0 = Some((level.title.clone(), level.par_moves));
+    add_edges(commands, graph);
+    loading.0 = None;
+}
+
+pub(crate) fn show_level_info(mut contexts: EguiContexts, info: Res<LevelInfo>) {
+    let Some((title, par_moves)) = &info.0 else {
+        return;
+    };
+    egui::Window::new("Level").show(contexts.ctx_mut(), |ui| {
+        ui.heading(title);
+        if let Some(par_moves) = par_moves {
+            ui.label(format!("Par: {par_moves} moves"));
+        }
+    });
+}
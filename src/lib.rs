@@ -0,0 +1,283 @@
+use planarity_core::difficulty::Difficulty;
+
+use crate::{
+    cli::{Cli, StartActions},
+    config::SavedSettings,
+    crossings::Crossings,
+    cursor::CursorWorldCoords,
+    generate::PuzzleSeed,
+    import::ImportQueue,
+    save::SavedGame,
+    story::story_assets::StoryFile,
+};
+pub use crate::{
+    config::Cfg,
+    generate::GenerationPlugin,
+    input::{Action, InteractionPlugin},
+    story::StoryPlugin,
+};
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_egui::EguiPlugin;
+use bevy_inspector_egui::{
+    inspector_options::ReflectInspectorOptions,
+    quick::{ResourceInspectorPlugin, WorldInspectorPlugin},
+    InspectorOptions,
+};
+use bevy_prototype_lyon::prelude::*;
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use iyes_progress::prelude::*;
+
+mod cli;
+mod config;
+mod crossings;
+mod cursor;
+mod daily;
+mod export;
+mod fields;
+mod generate;
+mod hint;
+mod history;
+mod import;
+mod input;
+mod level;
+mod save;
+mod story;
+#[cfg(test)]
+mod tests;
+
+/// Tags entities that will be deleted when resetting the level
+#[derive(Debug, Component)]
+struct LevelCleanup;
+
+/// Graph nodes
+#[derive(Debug, Component)]
+struct Node;
+
+#[derive(Debug, Component, PartialEq)]
+enum LineIntersects {
+    Unsolved,
+    Solved,
+    Intersecting,
+}
+
+#[derive(Resource, Default, Reflect, Clone, Debug, InspectorOptions)]
+#[reflect(Resource, InspectorOptions, Default)]
+struct IntersectionsCount(u32);
+
+#[derive(Debug, Component, PartialEq, Eq, Hash, Clone, Copy)]
+struct Edge(Entity, Entity);
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Velocity(Option<Vec2>);
+
+#[derive(Debug, Clone)]
+struct Endpoint(Entity, Vec2);
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+enum AppState {
+    #[default]
+    Loading,
+    Running,
+}
+
+/// The whole game, for adding to an app that already has [`DefaultPlugins`].
+/// Stories need the host's [`AssetPlugin`] to be in [`AssetMode::Processed`].
+pub struct PlanarityPlugin {
+    /// The settings to start with
+    pub cfg: Cfg,
+    /// The Ink story to play, as a path in the assets directory, or nothing to
+    /// go straight to free play
+    pub story: Option<String>,
+    pub interaction: InteractionPlugin,
+    /// Write the puzzle and settings changed in the inspector to disk, and
+    /// offer to resume a saved puzzle at startup
+    pub persistence: bool,
+    pub debug: Option<DebugPlugin>,
+}
+
+impl Default for PlanarityPlugin {
+    fn default() -> Self {
+        Self {
+            cfg: Cfg::default(),
+            story: None,
+            interaction: InteractionPlugin::default(),
+            persistence: false,
+            debug: None,
+        }
+    }
+}
+
+impl Plugin for PlanarityPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        if !app.is_plugin_added::<ShapePlugin>() {
+            app.add_plugins(ShapePlugin);
+        }
+        app.insert_resource(self.cfg.clone())
+            .init_resource::<IntersectionsCount>()
+            .init_resource::<StoryFile>()
+            .init_resource::<StartActions>()
+            .add_event::<Action>()
+            .add_state::<AppState>()
+            .add_plugins((
+                ProgressPlugin::new(AppState::Loading)
+                    .continue_to(AppState::Running)
+                    .track_assets(),
+                GenerationPlugin,
+                self.interaction.clone(),
+            ));
+        if let Some(story) = &self.story {
+            app.add_plugins(StoryPlugin {
+                story: story.clone(),
+            });
+        }
+        if self.persistence {
+            if !app.world.contains_resource::<SavedSettings>() {
+                app.insert_resource(SavedSettings(self.cfg.clone()));
+            }
+            app.insert_resource(SavedGame::from_disk())
+                .add_systems(Last, (save::save_game, config::save_settings))
+                .add_systems(
+                    Update,
+                    save::offer_resume.run_if(in_state(AppState::Running)),
+                );
+        }
+        if let Some(debug) = self.debug {
+            app.add_plugins(debug);
+        }
+    }
+}
+
+/// Gizmos and printouts turned on from the settings, and inspectors for the
+/// world and the game's resources
+#[derive(Clone, Copy, Debug)]
+pub struct DebugPlugin {
+    pub inspectors: bool,
+}
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                fields::show_strength,
+                fields::debug_field,
+                generate::debug_solution,
+            )
+                .run_if(in_state(AppState::Running)),
+        );
+        if self.inspectors {
+            app.add_plugins((
+                WorldInspectorPlugin::new(),
+                ResourceInspectorPlugin::<Cfg>::default(),
+                ResourceInspectorPlugin::<IntersectionsCount>::default(),
+                ResourceInspectorPlugin::<PuzzleSeed>::default(),
+                ResourceInspectorPlugin::<Difficulty>::default(),
+            ));
+        }
+    }
+}
+
+/// Run the game, with options from the command line
+pub fn run() {
+    let cli = Cli::parse();
+    let saved = Cfg::load();
+    let mut cfg = saved.clone();
+    if let Err(message) = cli.apply(&mut cfg) {
+        Cli::command()
+            .error(ErrorKind::InvalidValue, message)
+            .exit();
+    }
+
+    App::new()
+        // .insert_resource(Msaa::Sample4)
+        .insert_resource(ClearColor(Color::BLACK))
+        // options from the command line aren't saved as settings
+        .insert_resource(SavedSettings(saved))
+        .add_plugins((
+            DefaultPlugins.set(AssetPlugin {
+                mode: AssetMode::Processed,
+                ..default()
+            }),
+            PlanarityPlugin {
+                cfg,
+                story: (!cli.no_story).then(|| cli.story.clone()),
+                persistence: true,
+                debug: Some(DebugPlugin {
+                    inspectors: !cli.no_inspectors,
+                }),
+                ..default()
+            },
+        ))
+        // a graph file given on the command line is imported once the game starts
+        .insert_resource(ImportQueue(cli.graph.iter().cloned().collect()))
+        .insert_resource(cli.start_actions())
+        .run();
+}
+
+/// Restyle the edges whose crossings changed and the nodes at their ends. Nodes
+/// start out unsolved, and only show crossings from the frame after.
+fn highlight_edges(
+    mut edges: Query<&mut Stroke, With<Edge>>,
+    mut nodes: ParamSet<(
+        Query<Entity, (With<Node>, Changed<LineIntersects>)>,
+        Query<(&mut Fill, &mut LineIntersects), With<Node>>,
+    )>,
+    mut intersections_count: ResMut<IntersectionsCount>,
+    mut crossings: ResMut<Crossings>,
+    // nodes that were shown as unsolved last frame
+    mut fresh: Local<Vec<Entity>>,
+) {
+    intersections_count.0 = crossings.count() as u32;
+    let (changed_edges, mut changed_nodes) = crossings.take_changed();
+    for edge in changed_edges {
+        if let Ok(mut stroke) = edges.get_mut(edge) {
+            *stroke = if crossings.crosses_any(edge) {
+                Stroke::new(Color::RED, 3.0)
+            } else {
+                Stroke::color(Color::DARK_GREEN)
+            };
+        }
+    }
+
+    changed_nodes.extend(fresh.drain(..));
+    let reset = nodes.p0().iter().collect::<Vec<_>>();
+    let mut nodes = nodes.p1();
+    for node in reset {
+        if let Ok((mut fill, mut intersects)) = nodes.get_mut(node) {
+            if *intersects == LineIntersects::Unsolved {
+                *fill = Fill::color(Color::WHITE);
+                *intersects = LineIntersects::Solved;
+                changed_nodes.remove(&node);
+                fresh.push(node);
+            }
+        }
+    }
+    for node in changed_nodes {
+        if let Ok((mut fill, mut intersects)) = nodes.get_mut(node) {
+            if crossings.node_crosses(node) {
+                *fill = Fill::color(Color::PURPLE);
+                *intersects = LineIntersects::Intersecting;
+            } else {
+                *fill = Fill::color(Color::MIDNIGHT_BLUE);
+                *intersects = LineIntersects::Solved;
+            }
+        }
+    }
+}
+
+fn apply_velocity(
+    mut points: Query<(&mut Transform, &mut Velocity), (With<Node>, Changed<Velocity>)>,
+    cursor: Res<CursorWorldCoords>,
+) {
+    for (mut point, mut velocity) in &mut points {
+        if let Some(velocity) = velocity.0.take() {
+            let distance_to_cursor = (cursor.0 - point.translation.xy()).length_squared();
+            let speed = (distance_to_cursor / 10000.).clamp(0., 1.);
+            point.translation += velocity.extend(0.) * speed
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use bevy::{app::AppExit, math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use bevy_prototype_lyon::prelude::*;
+use bladeink::story::Story;
+use directories::ProjectDirs;
+use petgraph::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use planarity_core::difficulty::Difficulty;
+
+use crate::{
+    config::Cfg,
+    cursor::TrackCursor,
+    generate::{make_edge, make_node, PreviousGraphs, ReferenceSolution},
+    history::History,
+    import::Imported,
+    input::Action,
+    level::{LevelInfo, LoadingLevel, Pinned},
+    story::{story_assets::StoryFile, StoryOutput},
+    Edge, Endpoint, LevelCleanup, Node,
+};
+
+/// Everything needed to carry on with a puzzle after quitting
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct SaveFile {
+    /// Every node in any version of the level, including hidden ones
+    nodes: Vec<SavedNode>,
+    /// Pairs of indices into `nodes`
+    edges: Vec<(usize, usize)>,
+    bigger: Vec<SavedGraph>,
+    smaller: Vec<SavedGraph>,
+    solution: Vec<(usize, [f32; 2])>,
+    cfg: Cfg,
+    /// The title and par of a hand-made level
+    #[serde(default)]
+    level: Option<(String, Option<u32>)>,
+    /// The Ink story's state, as bladeink's JSON
+    #[serde(default)]
+    story: Option<String>,
+    #[serde(default)]
+    story_output: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedNode {
+    position: [f32; 2],
+    #[serde(default)]
+    pinned: bool,
+    /// Hidden nodes are waiting to be split out of another node
+    #[serde(default)]
+    hidden: bool,
+}
+
+/// A graph from [`PreviousGraphs`], with nodes as indices into
+/// [`SaveFile::nodes`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedGraph {
+    nodes: Vec<(usize, [f32; 2])>,
+    edges: Vec<(usize, usize)>,
+}
+
+/// A save found at startup that the player hasn't yet chosen whether to resume
+#[derive(Resource, Default, Debug)]
+pub(crate) struct SavedGame(pub Option<SaveFile>);
+
+impl SaveFile {
+    /// Read a save from RON, checking every node it refers to is in it
+    pub(crate) fn parse(text: &str) -> Result<Self, SaveFileError> {
+        let save: Self = ron::from_str(text)?;
+        save.validate()?;
+        Ok(save)
+    }
+
+    fn validate(&self) -> Result<(), SaveFileError> {
+        let count = self.nodes.len();
+        let graphs = self.bigger.iter().chain(&self.smaller);
+        let indices = self
+            .edges
+            .iter()
+            .chain(graphs.clone().flat_map(|graph| &graph.edges))
+            .flat_map(|&(start, end)| [start, end])
+            .chain(graphs.flat_map(|graph| graph.nodes.iter().map(|(node, _)| *node)))
+            .chain(self.solution.iter().map(|(node, _)| *node));
+        for index in indices {
+            if index >= count {
+                return Err(SaveFileError::MissingNode { index, count });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum SaveFileError {
+    #[error("Could not parse save: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("Node {index} isn't in the {count} saved")]
+    MissingNode { index: usize, count: usize },
+}
+
+impl SavedGame {
+    pub(crate) fn from_disk() -> Self {
+        let Some(path) = save_path() else {
+            return Self(None);
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => match SaveFile::parse(&text) {
+                Ok(save) => Self(Some(save)),
+                Err(error) => {
+                    eprintln!("Couldn't read save {}: {error}", path.display());
+                    Self(None)
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Self(None),
+            Err(error) => {
+                eprintln!("Couldn't read save {}: {error}", path.display());
+                Self(None)
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum SaveError {
+    #[error("Could not write save: {0}")]
+    Io(#[from] io::Error),
+    #[error("Could not write save: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("No directory to save to")]
+    NoDirectory,
+}
+
+fn save_path() -> Option<PathBuf> {
+    Some(
+        ProjectDirs::from("", "", "planarity")?
+            .data_dir()
+            .join("save.ron"),
+    )
+}
+
+fn write_save(save: &SaveFile) -> Result<PathBuf, SaveError> {
+    let path = save_path().ok_or(SaveError::NoDirectory)?;
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(
+        &path,
+        ron::ser::to_string_pretty(save, PrettyConfig::default())?,
+    )?;
+    Ok(path)
+}
+
+/// Save when asked to and when the game closes
+pub(crate) fn save_game(
+    mut actions: EventReader<Action>,
+    mut exits: EventReader<AppExit>,
+    saved: Res<SavedGame>,
+    vertices: Query<(Entity, &Transform, Option<&Node>, Option<&Pinned>), With<TrackCursor>>,
+    edges: Query<&Edge>,
+    previous_graphs: Res<PreviousGraphs>,
+    solution: Res<ReferenceSolution>,
+    cfg: Res<Cfg>,
+    level_info: Res<LevelInfo>,
+    story: Option<NonSend<Story>>,
+    story_output: Option<Res<StoryOutput>>,
+) {
+    let asked = actions.read().any(|action| *action == Action::Save);
+    // quitting before choosing whether to resume keeps the old save
+    let exiting = exits.read().next().is_some() && saved.0.is_none();
+    if !asked && !exiting {
+        return;
+    }
+
+    let mut indices = HashMap::new();
+    let nodes = vertices
+        .iter()
+        .enumerate()
+        .map(|(index, (entity, transform, node, pinned))| {
+            indices.insert(entity, index);
+            SavedNode {
+                position: transform.translation.xy().into(),
+                pinned: pinned.is_some(),
+                hidden: node.is_none(),
+            }
+        })
+        .collect();
+    let save_graph = |graph: &StableGraph<Endpoint, (), Undirected>| SavedGraph {
+        nodes: graph
+            .node_weights()
+            .filter_map(|Endpoint(entity, position)| {
+                Some((*indices.get(entity)?, (*position).into()))
+            })
+            .collect(),
+        edges: graph
+            .edge_references()
+            .filter_map(|edge| {
+                let (start, end) = (graph[edge.source()].0, graph[edge.target()].0);
+                Some((*indices.get(&start)?, *indices.get(&end)?))
+            })
+            .collect(),
+    };
+    let save = SaveFile {
+        edges: edges
+            .iter()
+            .filter_map(|Edge(start, end)| Some((*indices.get(start)?, *indices.get(end)?)))
+            .collect(),
+        bigger: previous_graphs.bigger.iter().map(save_graph).collect(),
+        smaller: previous_graphs.smaller.iter().map(save_graph).collect(),
+        solution: solution
+            .0
+            .iter()
+            .filter_map(|(entity, position)| Some((*indices.get(entity)?, (*position).into())))
+            .collect(),
+        nodes,
+        cfg: cfg.clone(),
+        level: level_info.0.clone(),
+        story: story.and_then(|story| match story.save_state() {
+            Ok(state) => Some(state),
+            Err(error) => {
+                eprintln!("Couldn't save story: {error}");
+                None
+            }
+        }),
+        story_output: story_output.map_or_else(String::new, |output| output.0.clone()),
+    };
+    match write_save(&save) {
+        Ok(path) => eprintln!("Saved game to {}", path.display()),
+        Err(error) => eprintln!("Couldn't save game: {error}"),
+    }
+}
+
+/// Ask whether to carry on with the saved game, if there is one
+pub(crate) fn offer_resume(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut saved: ResMut<SavedGame>,
+    level_entities: Query<Entity, With<LevelCleanup>>,
+    mut cfg: ResMut<Cfg>,
+    mut previous_graphs: ResMut<PreviousGraphs>,
+    mut solution: ResMut<ReferenceSolution>,
+    mut difficulty: ResMut<Difficulty>,
+    mut history: ResMut<History>,
+    mut level_info: ResMut<LevelInfo>,
+    mut loading: ResMut<LoadingLevel>,
+    mut imported: ResMut<Imported>,
+    story_file: Res<StoryFile>,
+    mut story: Option<NonSendMut<Story>>,
+    mut story_output: Option<ResMut<StoryOutput>>,
+) {
+    // the story's state can only be restored once it's loaded
+    if story_file.0.is_some() && story.is_none() {
+        return;
+    }
+    if saved.0.is_none() {
+        return;
+    }
+    let mut resume = None;
+    egui::Window::new("Resume").show(contexts.ctx_mut(), |ui| {
+        ui.label("Carry on with the puzzle from last time?");
+        ui.horizontal(|ui| {
+            if ui.button("Resume").clicked() {
+                resume = Some(true);
+            }
+            if ui.button("New game").clicked() {
+                resume = Some(false);
+            }
+        });
+    });
+    if resume != Some(true) {
+        if resume == Some(false) {
+            saved.0 = None;
+        }
+        return;
+    }
+    let Some(save) = saved.0.take() else {
+        return;
+    };
+
+    for entity in &level_entities {
+        commands.entity(entity).despawn();
+    }
+    let entities = save
+        .nodes
+        .iter()
+        .map(|node| {
+            let mut entity = commands.spawn(make_node(node.position.into()));
+            if node.pinned {
+                entity.insert((Pinned, Stroke::new(Color::GRAY, 3.)));
+            }
+            if node.hidden {
+                entity.insert(Visibility::Hidden).remove::<Node>();
+            }
+            entity.id()
+        })
+        .collect::<Vec<_>>();
+    let load_graph = |saved: &SavedGraph| {
+        let mut graph = StableGraph::<Endpoint, (), Undirected>::default();
+        let indices = saved
+            .nodes
+            .iter()
+            .map(|(node, position)| {
+                let index = graph.add_node(Endpoint(entities[*node], (*position).into()));
+                (*node, index)
+            })
+            .collect::<HashMap<_, _>>();
+        for (start, end) in &saved.edges {
+            if let (Some(start), Some(end)) = (indices.get(start), indices.get(end)) {
+                graph.add_edge(*start, *end, ());
+            }
+        }
+        graph
+    };
+    previous_graphs.bigger = save.bigger.iter().map(load_graph).collect();
+    previous_graphs.smaller = save.smaller.iter().map(load_graph).collect();
+    solution.0 = save
+        .solution
+        .iter()
+        .map(|(node, position)| (entities[*node], Vec2::from(*position)))
+        .collect();
+
+    let mut graph = StableGraph::<Vec2, (), Undirected>::default();
+    let indices = save
+        .nodes
+        .iter()
+        .map(|node| graph.add_node(node.position.into()))
+        .collect::<Vec<_>>();
+    for &(start, end) in &save.edges {
+        graph.add_edge(indices[start], indices[end], ());
+        let endpoint = |node: usize| Endpoint(entities[node], save.nodes[node].position.into());
+        make_edge(&mut commands, &endpoint(start), &endpoint(end));
+    }
+    graph.retain_nodes(|_, node| !save.nodes[node.index()].hidden);
+    *difficulty = Difficulty::of(&graph);
+
+    // the saved game's settings are for this run only, not to be saved
+    *cfg.bypass_change_detection() = save.cfg.validated();
+    level_info.0 = save.level;
+    if let (Some(state), Some(story), Some(story_output)) =
+        (&save.story, &mut story, &mut story_output)
+    {
+        match story.load_state(state) {
+            Ok(()) => story_output.0 = save.story_output,
+            Err(error) => eprintln!("Couldn't restore story: {error}"),
+        }
+    }
+    // a level the story asked for before resuming would replace this one
+    loading.0 = None;
+    imported.0 = None;
+    history.clear();
+}
@@ -1,9 +1,13 @@
 use std::{cell::RefCell, rc::Rc, str};
 
+use planarity_core::difficulty::Difficulty;
+
 use crate::{
+    generate::Generator,
+    hint::HintsUsed,
     input::Action,
-    story::story_assets::{JsonStoryAsset, StoryJson},
-    IntersectionsCount,
+    story::story_assets::{InkAssetPlugin, JsonStoryAsset, StoryFile, StoryJson},
+    AppState, IntersectionsCount,
 };
 use bevy::{ecs::system::SystemState, prelude::*};
 use bevy_egui::{egui, EguiContexts};
@@ -20,6 +24,34 @@ use tap::Tap;
 
 pub mod story_assets;
 
+/// The Ink story that frames the puzzles and picks which ones to play
+pub struct StoryPlugin {
+    /// The story's path in the assets directory
+    pub story: String,
+}
+
+impl Plugin for StoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StoryFile(Some(self.story.clone())))
+            .add_event::<Tag>()
+            .add_plugins(InkAssetPlugin)
+            .add_systems(Update, story_assets::setup_story_asset)
+            .add_systems(Update, setup_story.run_if(story_needs_reload))
+            .add_systems(
+                Update,
+                (
+                    (show_story, tag_actions).chain(),
+                    log_tags,
+                    update_intersections,
+                    update_hints,
+                    update_difficulty,
+                )
+                    .run_if(resource_exists::<StoryOutput>())
+                    .run_if(in_state(AppState::Running)),
+            );
+    }
+}
+
 #[derive(Resource, Default, Reflect, Clone, Debug, InspectorOptions)]
 #[reflect(Resource, InspectorOptions, Default)]
 pub(crate) struct StoryOutput(pub String);
@@ -104,6 +136,15 @@ pub(crate) fn tag_actions(mut tag_events: EventReader<Tag>, mut actions: EventWr
         match tag.as_str() {
             "RESET" => actions.send(Action::Reset),
             "ADD" => actions.send(Action::Bigger),
+            "REMOVE" => actions.send(Action::Smaller),
+            string if string.starts_with("LEVEL ") => {
+                let name = string.strip_prefix("LEVEL ").unwrap().trim();
+                actions.send(Action::Level(name.to_owned()))
+            }
+            "DAILY" => {
+                actions.send(Action::Daily);
+                actions.send(Action::Reset)
+            }
             string if string.starts_with("SIZE ") => {
                 if let Some((graph_size, number_of_circles)) = string
                     .strip_prefix("SIZE ")
@@ -116,6 +157,33 @@ pub(crate) fn tag_actions(mut tag_events: EventReader<Tag>, mut actions: EventWr
                     actions.send(Action::Size(graph_size, number_of_circles))
                 }
             }
+            // a fixed seed makes every puzzle after it the same, until `SEED RANDOM`
+            "SEED RANDOM" => actions.send(Action::Seed(None)),
+            string if string.starts_with("SEED ") => {
+                if let Ok(seed) = string.strip_prefix("SEED ").unwrap().trim().parse() {
+                    actions.send(Action::Seed(Some(seed)))
+                }
+            }
+            "DIFFICULTY ANY" => actions.send(Action::Difficulty(0, u32::MAX)),
+            string if string.starts_with("DIFFICULTY ") => {
+                if let Some((min, max)) = string
+                    .strip_prefix("DIFFICULTY ")
+                    .unwrap()
+                    .split(' ')
+                    .flat_map(str::parse)
+                    .tuples()
+                    .next()
+                {
+                    actions.send(Action::Difficulty(min, max))
+                }
+            }
+            string if string.starts_with("GENERATOR ") => {
+                let name = string.strip_prefix("GENERATOR ").unwrap().trim();
+                match Generator::from_name(name) {
+                    Some(generator) => actions.send(Action::Generator(generator)),
+                    None => eprintln!("Warning: unknown generator {name}"),
+                }
+            }
             _ => {}
         }
     }
@@ -133,6 +201,35 @@ pub(crate) fn update_intersections(
         .unwrap()
 }
 
+/// Like hints, stories without `VAR difficulty` are only warned about once
+pub(crate) fn update_difficulty(
+    mut story: NonSendMut<Story>,
+    difficulty: Res<Difficulty>,
+    mut warned: Local<bool>,
+) {
+    if let Err(error) = story.set_variable("difficulty", &ValueType::Float(difficulty.score)) {
+        if !*warned {
+            eprintln!("Couldn't tell the story the difficulty: {error}");
+            *warned = true;
+        }
+    }
+}
+
+/// Stories don't have to count hints, so one without `VAR hints` is only
+/// warned about the first time
+pub(crate) fn update_hints(
+    mut story: NonSendMut<Story>,
+    hints_used: Res<HintsUsed>,
+    mut warned: Local<bool>,
+) {
+    if let Err(error) = story.set_variable("hints", &ValueType::Int(hints_used.0 as i32)) {
+        if !*warned {
+            eprintln!("Couldn't tell the story about hints: {error}");
+            *warned = true;
+        }
+    }
+}
+
 struct Handler;
 impl ErrorHandler for Handler {
     fn error(&mut self, message: &str, error_type: ErrorType) {
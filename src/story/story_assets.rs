@@ -21,12 +21,21 @@ use std::{
 use tempfile::TempDir;
 use thiserror::Error;
 
+/// The Ink story to play, as a path in the assets directory, or nothing to
+/// skip the story
+#[derive(Resource, Default, Debug)]
+pub(crate) struct StoryFile(pub Option<String>);
+
 pub(crate) fn setup_story_asset(
     mut commands: Commands,
     server: Res<AssetServer>,
     mut loading: ResMut<AssetsLoading>,
+    story_file: Res<StoryFile>,
 ) {
-    let handle = server.load("main.ink");
+    let Some(path) = &story_file.0 else {
+        return;
+    };
+    let handle = server.load(path);
     commands.insert_resource(StoryJson(handle.clone()));
     loading.add(handle);
 }
@@ -0,0 +1,557 @@
+//! Gameplay systems run in an app with no window, on graphs with known layouts
+
+use bevy::{ecs::system::CommandQueue, prelude::*};
+use bevy_prototype_lyon::prelude::*;
+use planarity_core::difficulty::Difficulty;
+
+use crate::{
+    config::Cfg,
+    crossings::{self, Crossings},
+    daily::{self, Daily},
+    generate::{make_edge, make_node, Generator, PreviousGraphs, PuzzleSeed, ReferenceSolution},
+    highlight_edges,
+    input::{self, Action},
+    save::{SaveFile, SaveFileError},
+    story::{self, Tag},
+    Edge, Endpoint, IntersectionsCount, LineIntersects, Node,
+};
+
+/// An app with the game's systems but no rendering, input or assets
+struct Harness {
+    app: App,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(Cfg {
+                seed: Some(1),
+                ..default()
+            })
+            .init_resource::<IntersectionsCount>()
+            .init_resource::<Crossings>()
+            .init_resource::<PreviousGraphs>()
+            .init_resource::<ReferenceSolution>()
+            .init_resource::<PuzzleSeed>()
+            .init_resource::<Difficulty>()
+            .init_resource::<Daily>()
+            .add_event::<Action>()
+            .add_event::<Tag>()
+            .add_systems(
+                Update,
+                (
+                    (crossings::update_crossings, highlight_edges).chain(),
+                    (
+                        input::network_size,
+                        input::set_seed,
+                        input::set_difficulty,
+                        daily::start_daily,
+                        input::reset_network,
+                    )
+                        .chain(),
+                    input::bigger_network,
+                    input::smaller_network,
+                    story::tag_actions,
+                ),
+            );
+        Self { app }
+    }
+
+    /// Spawn nodes at `positions`, joined by `edges` between indices into it
+    fn with_graph(positions: &[[f32; 2]], edges: &[(usize, usize)]) -> (Self, Vec<Entity>) {
+        let mut harness = Self::new();
+        let world = &mut harness.app.world;
+        let endpoints = positions
+            .iter()
+            .map(|&position| {
+                Endpoint(
+                    world.spawn(make_node(position.into())).id(),
+                    position.into(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut queue = CommandQueue::default();
+        {
+            let mut commands = Commands::new(&mut queue, world);
+            for &(start, end) in edges {
+                make_edge(&mut commands, &endpoints[start], &endpoints[end]);
+            }
+        }
+        queue.apply(world);
+        let nodes = endpoints
+            .into_iter()
+            .map(|Endpoint(node, _)| node)
+            .collect();
+        (harness, nodes)
+    }
+
+    fn step(&mut self) {
+        self.app.update();
+    }
+
+    fn send<E: Event>(&mut self, event: E) {
+        self.app.world.send_event(event);
+    }
+
+    /// Every action sent in the last two frames
+    fn actions(&self) -> Vec<Action> {
+        let events = self.app.world.resource::<Events<Action>>();
+        events.get_reader().read(events).cloned().collect()
+    }
+
+    fn intersections(&self) -> u32 {
+        self.app.world.resource::<IntersectionsCount>().0
+    }
+
+    /// The colour and width of the edge between two nodes
+    fn stroke(&mut self, start: Entity, end: Entity) -> (Color, f32) {
+        let mut edges = self.app.world.query::<(&Edge, &Stroke)>();
+        edges
+            .iter(&self.app.world)
+            .find(|(edge, _)| **edge == Edge(start, end))
+            .map(|(_, stroke)| (stroke.color, stroke.options.line_width))
+            .expect("edge exists")
+    }
+
+    fn intersects(&self, node: Entity) -> &LineIntersects {
+        self.app.world.get::<LineIntersects>(node).unwrap()
+    }
+
+    fn move_node(&mut self, node: Entity, position: Vec2) {
+        let mut transform = self.app.world.get_mut::<Transform>(node).unwrap();
+        transform.translation = position.extend(1.);
+    }
+
+    fn count<F: bevy::ecs::query::ReadOnlyWorldQuery>(&mut self) -> usize {
+        self.app
+            .world
+            .query_filtered::<(), F>()
+            .iter(&self.app.world)
+            .count()
+    }
+}
+
+/// A square with both diagonals and one side, so only the diagonals cross
+fn crossed_square() -> (Harness, Vec<Entity>) {
+    Harness::with_graph(
+        &[[0., 0.], [100., 0.], [100., 100.], [0., 100.]],
+        &[(0, 2), (1, 3), (0, 1)],
+    )
+}
+
+#[test]
+fn counts_crossings() {
+    let (mut harness, _) = crossed_square();
+    harness.step();
+    assert_eq!(harness.intersections(), 1);
+
+    let (mut harness, _) = Harness::with_graph(
+        &[[0., 0.], [100., 0.], [50., 100.]],
+        &[(0, 1), (1, 2), (2, 0)],
+    );
+    harness.step();
+    assert_eq!(harness.intersections(), 0);
+}
+
+#[test]
+fn highlights_crossing_edges() {
+    let (mut harness, nodes) = crossed_square();
+    harness.step();
+    assert_eq!(harness.stroke(nodes[0], nodes[2]), (Color::RED, 3.));
+    assert_eq!(harness.stroke(nodes[1], nodes[3]), (Color::RED, 3.));
+    assert_eq!(harness.stroke(nodes[0], nodes[1]).0, Color::DARK_GREEN);
+}
+
+#[test]
+fn marks_nodes_on_crossing_edges() {
+    let (mut harness, nodes) = crossed_square();
+    // nodes start unsolved, and only show crossings from the next frame
+    harness.step();
+    for node in &nodes {
+        assert_eq!(*harness.intersects(*node), LineIntersects::Solved);
+    }
+    harness.step();
+    for node in &nodes {
+        assert_eq!(*harness.intersects(*node), LineIntersects::Intersecting);
+    }
+}
+
+#[test]
+fn moving_a_node_untangles() {
+    let (mut harness, nodes) = crossed_square();
+    harness.step();
+    harness.step();
+    // fold the square over so the diagonals become sides
+    harness.move_node(nodes[2], Vec2::new(0., 100.));
+    harness.move_node(nodes[3], Vec2::new(100., 100.));
+    harness.step();
+    assert_eq!(harness.intersections(), 0);
+    assert_eq!(harness.stroke(nodes[0], nodes[2]).0, Color::DARK_GREEN);
+    harness.step();
+    for node in &nodes {
+        assert_eq!(*harness.intersects(*node), LineIntersects::Solved);
+    }
+}
+
+#[test]
+fn levels_on_a_grid_show_their_crossings() {
+    // the first edge starts on the third, right beside where it crosses the
+    // second, as happens in hand-made levels
+    let (mut harness, nodes) = Harness::with_graph(
+        &[
+            [100., 0.],
+            [300., 100.],
+            [300., 0.],
+            [0., 200.],
+            [0., 0.],
+            [200., 0.],
+        ],
+        &[(0, 1), (2, 3), (4, 5)],
+    );
+    harness.step();
+    assert!(harness.intersections() >= 1);
+    assert_eq!(harness.stroke(nodes[0], nodes[1]).0, Color::RED);
+    assert_eq!(harness.stroke(nodes[2], nodes[3]).0, Color::RED);
+}
+
+#[test]
+fn dragging_onto_a_long_edge_finds_the_crossing() {
+    // a long diagonal, a short edge off to one side, and a few more far away
+    // so moving one node only updates the edges at it
+    let (mut harness, nodes) = Harness::with_graph(
+        &[
+            [0., 0.],
+            [2000., 1800.],
+            [-500., 900.],
+            [-500., 1000.],
+            [3000., 0.],
+            [3100., 0.],
+            [3000., 100.],
+            [3100., 100.],
+        ],
+        &[(0, 1), (2, 3), (4, 5), (5, 7), (7, 6), (6, 4)],
+    );
+    harness.step();
+    assert_eq!(harness.intersections(), 0);
+    // the middle of the diagonal is at (1000, 900)
+    harness.move_node(nodes[2], Vec2::new(1000., 850.));
+    harness.move_node(nodes[3], Vec2::new(1000., 950.));
+    harness.step();
+    assert_eq!(harness.intersections(), 1);
+    assert_eq!(harness.stroke(nodes[0], nodes[1]).0, Color::RED);
+}
+
+#[test]
+fn only_restyles_what_changed() {
+    // the crossed square, with a triangle off to one side
+    let (mut harness, nodes) = Harness::with_graph(
+        &[
+            [0., 0.],
+            [100., 0.],
+            [100., 100.],
+            [0., 100.],
+            [500., 0.],
+            [600., 0.],
+            [550., 100.],
+        ],
+        &[(0, 2), (1, 3), (0, 1), (4, 5), (5, 6), (6, 4)],
+    );
+    harness.step();
+    harness.step();
+    let mut edges = harness.app.world.query::<(&Edge, &mut Stroke)>();
+    for (edge, mut stroke) in edges.iter_mut(&mut harness.app.world) {
+        if *edge == Edge(nodes[4], nodes[5]) {
+            *stroke = Stroke::color(Color::GOLD);
+        }
+    }
+
+    harness.move_node(nodes[3], Vec2::new(200., 100.));
+    harness.step();
+    assert_eq!(harness.intersections(), 0);
+    assert_eq!(harness.stroke(nodes[1], nodes[3]).0, Color::DARK_GREEN);
+    assert_eq!(harness.stroke(nodes[4], nodes[5]).0, Color::GOLD);
+    assert_eq!(*harness.intersects(nodes[0]), LineIntersects::Solved);
+}
+
+#[test]
+fn reset_makes_a_new_puzzle() {
+    let (mut harness, nodes) = crossed_square();
+    harness.send(Action::Reset);
+    harness.step();
+    for node in nodes {
+        assert!(harness.app.world.get_entity(node).is_none());
+    }
+    assert_eq!(harness.app.world.resource::<PuzzleSeed>().0, 1);
+    let cfg = harness.app.world.resource::<Cfg>().clone();
+    assert_eq!(harness.count::<With<Node>>(), cfg.limit_nodes);
+
+    harness.step();
+    assert!(harness.intersections() >= cfg.min_crossings);
+}
+
+#[test]
+fn bigger_splits_nodes_back_out() {
+    let mut harness = Harness::new();
+    harness.send(Action::Reset);
+    harness.step();
+    let bigger = harness
+        .app
+        .world
+        .resource::<PreviousGraphs>()
+        .bigger
+        .last()
+        .unwrap();
+    let (node_count, edge_count) = (bigger.node_count(), bigger.edge_count());
+
+    harness.send(Action::Bigger);
+    harness.step();
+    assert_eq!(harness.count::<With<Node>>(), node_count);
+    assert_eq!(harness.count::<With<Edge>>(), edge_count);
+    assert_eq!(
+        harness.app.world.resource::<PreviousGraphs>().smaller.len(),
+        1
+    );
+
+    harness.send(Action::Smaller);
+    harness.step();
+    assert_eq!(
+        harness.count::<With<Node>>(),
+        harness.app.world.resource::<Cfg>().limit_nodes
+    );
+}
+
+#[test]
+fn daily_leaves_the_settings_alone() {
+    let mut harness = Harness::new();
+    let cfg = harness.app.world.resource::<Cfg>().clone();
+    harness.send(Action::Daily);
+    harness.send(Action::Reset);
+    harness.step();
+
+    let (seed, generate_config) = harness
+        .app
+        .world
+        .resource::<Daily>()
+        .0
+        .as_ref()
+        .expect("daily run started")
+        .puzzle();
+    assert_eq!(harness.app.world.resource::<PuzzleSeed>().0, seed);
+    assert_eq!(harness.count::<With<Node>>(), generate_config.limit_nodes);
+    let after = harness.app.world.resource::<Cfg>();
+    assert_eq!(after.seed, cfg.seed);
+    assert_eq!(
+        (after.num_circles, after.limit_nodes),
+        (cfg.num_circles, cfg.limit_nodes)
+    );
+
+    // changing the puzzle abandons the daily, and goes back to the settings
+    harness.send(Action::Seed(Some(3)));
+    harness.send(Action::Reset);
+    harness.step();
+    assert!(harness.app.world.resource::<Daily>().0.is_none());
+    assert_eq!(harness.app.world.resource::<PuzzleSeed>().0, 3);
+
+    // so does a reset on its own
+    harness.send(Action::Daily);
+    harness.send(Action::Reset);
+    harness.step();
+    assert!(harness.app.world.resource::<Daily>().0.is_some());
+    harness.send(Action::Reset);
+    harness.step();
+    assert!(harness.app.world.resource::<Daily>().0.is_none());
+}
+
+#[test]
+fn daily_is_the_same_whatever_the_settings() {
+    let puzzle = |cfg: Cfg| {
+        let mut harness = Harness::new();
+        harness.app.insert_resource(cfg);
+        harness.send(Action::Daily);
+        harness.send(Action::Reset);
+        harness.step();
+        let mut nodes = harness.app.world.query_filtered::<&Transform, With<Node>>();
+        nodes
+            .iter(&harness.app.world)
+            .map(|transform| transform.translation.truncate().to_array())
+            .collect::<Vec<_>>()
+    };
+    let first = puzzle(Cfg::default());
+    assert_eq!(
+        first,
+        puzzle(Cfg {
+            node_starting_distance: 300.,
+            node_starting_random_offset: 0.,
+            num_circles: 2,
+            generator: Generator::Grid,
+            ..Cfg::default()
+        })
+    );
+}
+
+#[test]
+fn actions_dont_count_as_setting_changes() {
+    #[derive(Resource, Default)]
+    struct SettingsChanged(bool);
+
+    fn record(cfg: Res<Cfg>, mut changed: ResMut<SettingsChanged>) {
+        changed.0 |= cfg.is_changed() && !cfg.is_added();
+    }
+
+    let mut harness = Harness::new();
+    harness
+        .app
+        .init_resource::<SettingsChanged>()
+        .add_systems(Last, record);
+    harness.step();
+    for action in [
+        Action::Size(8, 5),
+        Action::Seed(Some(42)),
+        Action::Difficulty(2, 10),
+    ] {
+        harness.send(action);
+    }
+    harness.step();
+    let cfg = harness.app.world.resource::<Cfg>();
+    assert_eq!((cfg.limit_nodes, cfg.seed), (8, Some(42)));
+    assert!(!harness.app.world.resource::<SettingsChanged>().0);
+
+    harness.app.world.resource_mut::<Cfg>().move_speed = 5.;
+    harness.step();
+    assert!(harness.app.world.resource::<SettingsChanged>().0);
+}
+
+#[test]
+fn only_changed_settings_are_copied() {
+    let before = Cfg::default();
+    let after = Cfg {
+        move_speed: 5.,
+        seed: Some(3),
+        generator: Generator::Grid,
+        ..before.clone()
+    };
+    let mut saved = Cfg {
+        num_circles: 4,
+        ..Cfg::default()
+    };
+    saved.copy_changes(&before, &after);
+    assert_eq!(
+        (
+            saved.move_speed,
+            saved.seed,
+            saved.num_circles,
+            saved.generator
+        ),
+        (5., Some(3), 4, Generator::Grid)
+    );
+}
+
+#[test]
+fn settings_can_be_set_by_name() {
+    let mut cfg = Cfg::default();
+    cfg.set("generator=Delaunay").unwrap();
+    cfg.set("seed=Some(7)").unwrap();
+    assert_eq!((cfg.generator, cfg.seed), (Generator::Delaunay, Some(7)));
+    assert!(cfg.set("nothing=1").is_err());
+    assert!(cfg.set("num_circles=3, limit_nodes: 4").is_err());
+    assert!(cfg.set("generator=Nowhere").is_err());
+    assert_eq!(cfg.generator, Generator::Delaunay);
+}
+
+#[test]
+fn validation_rejects_an_inverted_difficulty_band() {
+    let cfg = Cfg {
+        min_difficulty: 10.,
+        max_difficulty: 2.,
+        ..Cfg::default()
+    }
+    .validated();
+    let default = Cfg::default();
+    assert_eq!(
+        (cfg.min_difficulty, cfg.max_difficulty),
+        (default.min_difficulty, default.max_difficulty)
+    );
+
+    let cfg = Cfg {
+        min_difficulty: 2.,
+        max_difficulty: 10.,
+        ..Cfg::default()
+    }
+    .validated();
+    assert_eq!((cfg.min_difficulty, cfg.max_difficulty), (2., 10.));
+}
+
+#[test]
+fn size_changes_the_settings() {
+    let mut harness = Harness::new();
+    harness.send(Action::Size(8, 5));
+    harness.step();
+    let cfg = harness.app.world.resource::<Cfg>();
+    assert_eq!((cfg.limit_nodes, cfg.num_circles), (8, 5));
+}
+
+#[test]
+fn every_setting_in_a_frame_applies_before_the_reset() {
+    let mut harness = Harness::new();
+    harness.send(Action::Seed(Some(9)));
+    harness.send(Action::Generator(Generator::Grid));
+    harness.send(Action::Difficulty(1, 50));
+    harness.send(Action::Reset);
+    harness.step();
+    let cfg = harness.app.world.resource::<Cfg>();
+    assert_eq!((cfg.seed, cfg.generator), (Some(9), Generator::Grid));
+    assert_eq!((cfg.min_difficulty, cfg.max_difficulty), (1., 50.));
+    assert_eq!(harness.app.world.resource::<PuzzleSeed>().0, 9);
+}
+
+#[test]
+fn tags_become_actions() {
+    let mut harness = Harness::new();
+    for tag in [
+        "SIZE 8 5",
+        "SEED 42",
+        "GENERATOR grid",
+        "DIFFICULTY 2 10",
+        "LEVEL tutorial",
+        "UNKNOWN TAG",
+        "RESET",
+    ] {
+        harness.send(Tag(tag.to_owned()));
+    }
+    harness.step();
+    assert_eq!(
+        harness.actions(),
+        [
+            Action::Size(8, 5),
+            Action::Seed(Some(42)),
+            Action::Generator(Generator::Grid),
+            Action::Difficulty(2, 10),
+            Action::Level("tutorial".to_owned()),
+            Action::Reset,
+        ]
+    );
+
+    // the actions may only be handled on the next frame
+    harness.step();
+    let cfg = harness.app.world.resource::<Cfg>();
+    assert_eq!((cfg.limit_nodes, cfg.num_circles), (8, 5));
+    assert_eq!(cfg.seed, Some(42));
+    assert_eq!(cfg.generator, Generator::Grid);
+    assert_eq!(harness.app.world.resource::<PuzzleSeed>().0, 42);
+}
+
+#[test]
+fn saves_with_missing_nodes_are_rejected() {
+    let save = |edges: &str| {
+        SaveFile::parse(&format!(
+            "(nodes: [(position: (0, 0)), (position: (1, 1))], edges: {edges}, \
+             bigger: [], smaller: [], solution: [(1, (0, 0))], cfg: ())"
+        ))
+    };
+    assert!(save("[(0, 1)]").is_ok());
+    assert!(matches!(
+        save("[(0, 2)]"),
+        Err(SaveFileError::MissingNode { index: 2, count: 2 })
+    ));
+}